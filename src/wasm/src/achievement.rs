@@ -0,0 +1,143 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Difficulty;
+use crate::storage::Storage;
+
+const ACHIEVEMENT_STORAGE_KEY: &str = "barrage.achievements";
+
+// Run Stats : what a finished run did, from the world
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunStats {
+    pub cleared: bool,
+    pub difficulty: Difficulty,
+    pub milli_secondtime: i32,
+    pub max_bullets: usize, // most bullets on screen at once
+    pub super_count: i32,   // super mode activations
+    pub misses: i32,        // hits taken
+    pub graze: i32,
+}
+
+// Condition : when an achievement unlocks
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Condition {
+    Clear,
+    ClearOn(Difficulty),
+    ClearWithoutSuper,
+    ClearUnder(i32),         // ms
+    ClearWithBullets(usize), // at least this many bullets on screen at once
+    NoMissClear,
+    Graze(i32), // grazes in one run, cleared or not
+}
+
+impl Condition {
+    fn is_met(&self, run: &RunStats) -> bool {
+        match *self {
+            Condition::Clear => run.cleared,
+            Condition::ClearOn(_difficulty) => run.cleared && run.difficulty == _difficulty,
+            Condition::ClearWithoutSuper => run.cleared && run.super_count == 0,
+            Condition::ClearUnder(_time) => run.cleared && run.milli_secondtime < _time,
+            Condition::ClearWithBullets(_bullets) => run.cleared && run.max_bullets >= _bullets,
+            Condition::NoMissClear => run.cleared && run.misses == 0,
+            Condition::Graze(_graze) => run.graze >= _graze,
+        }
+    }
+}
+
+// Achievement
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Achievement {
+    pub id: &'static str, // saved key, never rename
+    pub name: &'static str,
+    pub description: &'static str,
+    pub condition: Condition,
+}
+
+pub const ACHIEVEMENTS: [Achievement; 8] = [
+    Achievement {
+        id: "clear",
+        name: "Survivor",
+        description: "Clear the game",
+        condition: Condition::Clear,
+    },
+    Achievement {
+        id: "clear_hard",
+        name: "Hardened",
+        description: "Clear on Hard",
+        condition: Condition::ClearOn(Difficulty::Hard),
+    },
+    Achievement {
+        id: "clear_lunatic",
+        name: "Lunatic",
+        description: "Clear on Lunatic",
+        condition: Condition::ClearOn(Difficulty::Lunatic),
+    },
+    Achievement {
+        id: "no_super",
+        name: "Purist",
+        description: "Clear without super mode",
+        condition: Condition::ClearWithoutSuper,
+    },
+    Achievement {
+        id: "speed",
+        name: "Speedrunner",
+        description: "Clear in under 60 seconds",
+        condition: Condition::ClearUnder(60_000),
+    },
+    Achievement {
+        id: "bullet_hell",
+        name: "Bullet Hell",
+        description: "Clear after 400 bullets were on screen",
+        condition: Condition::ClearWithBullets(400),
+    },
+    Achievement {
+        id: "no_miss",
+        name: "Untouchable",
+        description: "Clear without getting hit",
+        condition: Condition::NoMissClear,
+    },
+    Achievement {
+        id: "graze",
+        name: "Grazer",
+        description: "Graze 300 bullets in one run",
+        condition: Condition::Graze(300),
+    },
+];
+
+// Achievements : unlocked ids, kept across sessions
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Achievements {
+    unlocked: Vec<String>,
+}
+
+impl Achievements {
+    pub fn load(storage: &dyn Storage) -> Self {
+        storage
+            .load(ACHIEVEMENT_STORAGE_KEY)
+            .and_then(|_json| serde_json::from_str(&_json).ok())
+            .unwrap_or_default()
+    }
+    pub fn save(&self, storage: &dyn Storage) {
+        if let Ok(_json) = serde_json::to_string(self) {
+            storage.save(ACHIEVEMENT_STORAGE_KEY, &_json);
+        }
+    }
+    pub fn is_unlocked(&self, achievement: &Achievement) -> bool {
+        self.unlocked.iter().any(|id| id == achievement.id)
+    }
+    /**
+     * unlock what a finished run earned, returns the newly unlocked achievements
+     */
+    pub fn evaluate(&mut self, run: &RunStats) -> Vec<&'static Achievement> {
+        let _unlocked: Vec<&'static Achievement> = ACHIEVEMENTS
+            .iter()
+            .filter(|a| !self.is_unlocked(a) && a.condition.is_met(run))
+            .collect();
+        self.unlocked
+            .extend(_unlocked.iter().map(|a| a.id.to_string()));
+        _unlocked
+    }
+}
@@ -0,0 +1,445 @@
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::music::{Channel, DECKS};
+use crate::world::GameEvent;
+use web_sys::{
+    AudioBuffer, AudioContext, AudioContextState, AudioParam, AudioScheduledSourceNode, GainNode,
+    OscillatorType, window,
+};
+
+pub(crate) const OUTPUT_GAIN: f32 = 0.2; // master gain at full volume, square waves are loud
+const NOISE_LENGTH: f64 = 1.0; // seconds of white noise, longer sounds loop it
+pub(crate) const NOISE_FREQ: f32 = 4000.0; // noise "pitch" of a playback rate of 1.0
+pub(crate) const NOISE_DECAY: f64 = 0.12; // seconds, music noise notes are drum hits
+pub(crate) const NOTE_RELEASE: f64 = 0.03; // seconds, fade out of a stopped music note
+
+// Waveform : the sound generators of the old sound chips
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Noise,
+}
+
+// Voice : one synthesized sound, a pitch sweep under a decaying envelope
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Voice {
+    pub waveform: Waveform,
+    pub from_freq: f32,
+    pub to_freq: f32,
+    pub duration: f64, // seconds
+    pub volume: f32,   // 0.0 - 1.0
+    pub interval: f64, // seconds before the same effect plays again
+}
+
+// Sfx : sound effects of the game
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Sfx {
+    Shot,
+    BulletCancel,
+    BossHit,
+    BossExplosion,
+    SuperReady,
+    SuperActivated,
+    Death,
+    Clear,
+    Unlock,
+}
+
+impl Sfx {
+    /**
+     * the sound of a world event, if it has one
+     */
+    pub fn for_event(event: &GameEvent) -> Option<Sfx> {
+        match event {
+            GameEvent::Start => None,
+            GameEvent::SuperReady => Some(Sfx::SuperReady),
+            GameEvent::SuperActivated => Some(Sfx::SuperActivated),
+            GameEvent::ShotFired => Some(Sfx::Shot),
+            GameEvent::BulletCancel => Some(Sfx::BulletCancel),
+            GameEvent::BossHit => Some(Sfx::BossHit),
+            GameEvent::BossDefeated => Some(Sfx::BossExplosion),
+            GameEvent::Clear => Some(Sfx::Clear),
+            GameEvent::Gameover => Some(Sfx::Death),
+            GameEvent::Abandoned(_) => None,
+        }
+    }
+    pub fn voice(&self) -> Voice {
+        match self {
+            Sfx::Shot => Voice {
+                waveform: Waveform::Square,
+                from_freq: 1760.0,
+                to_freq: 880.0,
+                duration: 0.04,
+                volume: 0.15,
+                interval: 0.08,
+            },
+            Sfx::BulletCancel => Voice {
+                waveform: Waveform::Noise,
+                from_freq: 8000.0,
+                to_freq: 2000.0,
+                duration: 0.08,
+                volume: 0.3,
+                interval: 0.06,
+            },
+            Sfx::BossHit => Voice {
+                waveform: Waveform::Triangle,
+                from_freq: 220.0,
+                to_freq: 110.0,
+                duration: 0.06,
+                volume: 0.6,
+                interval: 0.1,
+            },
+            Sfx::BossExplosion => Voice {
+                waveform: Waveform::Noise,
+                from_freq: 3000.0,
+                to_freq: 100.0,
+                duration: 1.5,
+                volume: 1.0,
+                interval: 0.0,
+            },
+            Sfx::SuperReady => Voice {
+                waveform: Waveform::Square,
+                from_freq: 440.0,
+                to_freq: 660.0,
+                duration: 0.15,
+                volume: 0.5,
+                interval: 0.0,
+            },
+            Sfx::SuperActivated => Voice {
+                waveform: Waveform::Square,
+                from_freq: 220.0,
+                to_freq: 880.0,
+                duration: 0.4,
+                volume: 0.5,
+                interval: 0.0,
+            },
+            Sfx::Death => Voice {
+                waveform: Waveform::Noise,
+                from_freq: 2000.0,
+                to_freq: 200.0,
+                duration: 1.0,
+                volume: 1.0,
+                interval: 0.0,
+            },
+            Sfx::Clear => Voice {
+                waveform: Waveform::Sawtooth,
+                from_freq: 330.0,
+                to_freq: 1320.0,
+                duration: 1.2,
+                volume: 0.5,
+                interval: 0.0,
+            },
+            Sfx::Unlock => Voice {
+                waveform: Waveform::Square,
+                from_freq: 660.0,
+                to_freq: 990.0,
+                duration: 0.3,
+                volume: 0.5,
+                interval: 0.0,
+            },
+        }
+    }
+}
+
+// Mixer : channel volumes, 0.0 - 1.0
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Mixer {
+    pub master: f32,
+    pub sfx: f32,
+    pub music: f32,
+}
+
+impl Default for Mixer {
+    fn default() -> Self {
+        Mixer {
+            master: 1.0,
+            sfx: 1.0,
+            music: 1.0,
+        }
+    }
+}
+
+/**
+ * sound output, the world only reports events, the front end decides what they sound like
+ */
+pub trait Audio: std::fmt::Debug {
+    fn play(&self, sfx: Sfx);
+    fn set_mixer(&self, mixer: Mixer);
+    // music: a deck plays one track, one note per channel
+    fn note_on(&self, deck: usize, channel: Channel, freq: f32);
+    fn note_off(&self, deck: usize, channel: Channel);
+    fn set_deck_volume(&self, deck: usize, volume: f32);
+    // a key or pointer press: the only time a browser lets sound start
+    fn unlock(&self);
+}
+
+// silence: for headless runs and browsers without Web Audio
+
+#[derive(Debug, Default)]
+pub struct NullAudio;
+
+impl Audio for NullAudio {
+    fn play(&self, _sfx: Sfx) {}
+    fn set_mixer(&self, _mixer: Mixer) {}
+    fn note_on(&self, _deck: usize, _channel: Channel, _freq: f32) {}
+    fn note_off(&self, _deck: usize, _channel: Channel) {}
+    fn set_deck_volume(&self, _deck: usize, _volume: f32) {}
+    fn unlock(&self) {}
+}
+
+// Web Audio : the graph is
+//
+//   effects ------------> sfx gain ---+
+//   notes -> deck gain -> music gain -+-> master gain -> speakers
+
+#[derive(Debug)]
+struct Output {
+    context: AudioContext,
+    master: GainNode,
+    sfx: GainNode,
+    music: GainNode,
+    decks: Vec<GainNode>,
+    noise: AudioBuffer,
+    last_played: HashMap<Sfx, f64>, // context time an effect last started
+    notes: HashMap<(usize, Channel), (AudioScheduledSourceNode, GainNode)>, // playing notes
+}
+
+impl Output {
+    fn new() -> Option<Self> {
+        let _context = AudioContext::new().ok()?;
+        let _master = _context.create_gain().ok()?;
+        let _sfx = _context.create_gain().ok()?;
+        let _music = _context.create_gain().ok()?;
+        _master
+            .connect_with_audio_node(&_context.destination())
+            .ok()?;
+        _sfx.connect_with_audio_node(&_master).ok()?;
+        _music.connect_with_audio_node(&_master).ok()?;
+        let mut _decks = vec![];
+        for _ in 0..DECKS {
+            let _deck = _context.create_gain().ok()?;
+            _deck.connect_with_audio_node(&_music).ok()?;
+            _decks.push(_deck);
+        }
+
+        // white noise, shared by every noise voice
+
+        let _sample_rate = _context.sample_rate();
+        let mut _seed: u32 = 0x1234_5678;
+        let _samples: Vec<f32> = (0..(_sample_rate as f64 * NOISE_LENGTH) as usize)
+            .map(|_| {
+                _seed ^= _seed << 13;
+                _seed ^= _seed >> 17;
+                _seed ^= _seed << 5;
+                _seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+            })
+            .collect();
+        let _noise = _context
+            .create_buffer(1, _samples.len() as u32, _sample_rate)
+            .ok()?;
+        _noise.copy_to_channel(&_samples, 0).ok()?;
+
+        Some(Output {
+            context: _context,
+            master: _master,
+            sfx: _sfx,
+            music: _music,
+            decks: _decks,
+            noise: _noise,
+            last_played: HashMap::new(),
+            notes: HashMap::new(),
+        })
+    }
+
+    /**
+     * oscillator or looped noise, with its pitch parameter and the frequency scale of that parameter
+     */
+    fn create_generator(
+        &self,
+        waveform: Waveform,
+    ) -> Option<(AudioScheduledSourceNode, AudioParam, f32)> {
+        if waveform == Waveform::Noise {
+            let _source = self.context.create_buffer_source().ok()?;
+            _source.set_buffer(Some(&self.noise));
+            _source.set_loop(true);
+            let _rate = _source.playback_rate();
+            return Some((_source.into(), _rate, 1.0 / NOISE_FREQ));
+        }
+        let _oscillator = self.context.create_oscillator().ok()?;
+        _oscillator.set_type(match waveform {
+            Waveform::Triangle => OscillatorType::Triangle,
+            Waveform::Sawtooth => OscillatorType::Sawtooth,
+            _ => OscillatorType::Square,
+        });
+        let _frequency = _oscillator.frequency();
+        Some((_oscillator.into(), _frequency, 1.0))
+    }
+
+    fn set_mixer(&self, mixer: Mixer) {
+        self.master.gain().set_value(mixer.master * OUTPUT_GAIN);
+        self.sfx.gain().set_value(mixer.sfx);
+        self.music.gain().set_value(mixer.music);
+    }
+
+    fn play(&mut self, sfx: Sfx) -> Option<()> {
+        let _voice = sfx.voice();
+        let _now = self.context.current_time();
+        if let Some(_last) = self.last_played.get(&sfx)
+            && _now - _last < _voice.interval
+        {
+            return Some(());
+        }
+        self.last_played.insert(sfx, _now);
+        let _end = _now + _voice.duration;
+
+        // envelope
+
+        let _gain = self.context.create_gain().ok()?;
+        _gain.gain().set_value_at_time(_voice.volume, _now).ok()?;
+        _gain
+            .gain()
+            .exponential_ramp_to_value_at_time(0.001, _end)
+            .ok()?;
+        _gain.connect_with_audio_node(&self.sfx).ok()?;
+
+        // generator
+
+        let (_source, _pitch, _scale) = self.create_generator(_voice.waveform)?;
+        _pitch
+            .set_value_at_time(_voice.from_freq * _scale, _now)
+            .ok()?;
+        _pitch
+            .exponential_ramp_to_value_at_time(_voice.to_freq * _scale, _end)
+            .ok()?;
+        _source.connect_with_audio_node(&_gain).ok()?;
+        _source.start().ok()?;
+        _source.stop_with_when(_end).ok()?;
+        Some(())
+    }
+
+    fn note_on(&mut self, deck: usize, channel: Channel, freq: f32) -> Option<()> {
+        self.note_off(deck, channel);
+        let _now = self.context.current_time();
+        let _gain = self.context.create_gain().ok()?;
+        _gain.gain().set_value_at_time(channel.level(), _now).ok()?;
+        _gain.connect_with_audio_node(self.decks.get(deck)?).ok()?;
+        let (_source, _pitch, _scale) = self.create_generator(channel.waveform())?;
+        _pitch.set_value_at_time(freq * _scale, _now).ok()?;
+        _source.connect_with_audio_node(&_gain).ok()?;
+        _source.start().ok()?;
+        if channel == Channel::Noise {
+            _gain
+                .gain()
+                .exponential_ramp_to_value_at_time(0.001, _now + NOISE_DECAY)
+                .ok()?;
+            _source.stop_with_when(_now + NOISE_DECAY).ok()?;
+        }
+        self.notes.insert((deck, channel), (_source, _gain));
+        Some(())
+    }
+
+    fn note_off(&mut self, deck: usize, channel: Channel) -> Option<()> {
+        let (_source, _gain) = self.notes.remove(&(deck, channel))?;
+        let _now = self.context.current_time();
+        let _level = _gain.gain();
+        _level.cancel_scheduled_values(_now).ok()?;
+        _level.set_value_at_time(_level.value(), _now).ok()?;
+        _level
+            .linear_ramp_to_value_at_time(0.0, _now + NOTE_RELEASE)
+            .ok()?;
+        _source.stop_with_when(_now + NOTE_RELEASE).ok()?;
+        Some(())
+    }
+}
+
+// browser Web Audio, the context is created & resumed from a user gesture, silent until then
+
+#[derive(Debug)]
+pub struct WebAudio {
+    output: RefCell<Option<Output>>,
+    mixer: Cell<Mixer>,
+}
+
+impl WebAudio {
+    /**
+     * the output once it is running, nothing is played before the first gesture or while suspended
+     */
+    fn with_output(&self, f: impl FnOnce(&mut Output)) {
+        if let Some(_output) = self.output.borrow_mut().as_mut()
+            && _output.context.state() == AudioContextState::Running
+        {
+            f(_output);
+        }
+    }
+}
+
+impl Audio for WebAudio {
+    fn play(&self, sfx: Sfx) {
+        self.with_output(|o| {
+            o.play(sfx);
+        });
+    }
+    fn set_mixer(&self, mixer: Mixer) {
+        self.mixer.set(mixer);
+        if let Some(_output) = self.output.borrow().as_ref() {
+            _output.set_mixer(mixer);
+        }
+    }
+    fn note_on(&self, deck: usize, channel: Channel, freq: f32) {
+        self.with_output(|o| {
+            o.note_on(deck, channel, freq);
+        });
+    }
+    fn note_off(&self, deck: usize, channel: Channel) {
+        if let Some(_output) = self.output.borrow_mut().as_mut() {
+            _output.note_off(deck, channel);
+        }
+    }
+    fn set_deck_volume(&self, deck: usize, volume: f32) {
+        if let Some(_deck) = self
+            .output
+            .borrow()
+            .as_ref()
+            .and_then(|o| o.decks.get(deck))
+        {
+            _deck.gain().set_value(volume);
+        }
+    }
+    fn unlock(&self) {
+        let mut _output = self.output.borrow_mut();
+        if _output.is_none() {
+            *_output = Output::new();
+            if let Some(_output) = _output.as_ref() {
+                _output.set_mixer(self.mixer.get());
+            }
+        }
+        if let Some(_output) = _output.as_ref()
+            && _output.context.state() == AudioContextState::Suspended
+        {
+            // the state turns to running when the promise resolves, sounds wait for it
+            let _ = _output.context.resume();
+        }
+    }
+}
+
+/**
+ * Web Audio in a browser, otherwise silence, always silence in native runs
+ */
+pub fn open() -> Rc<dyn Audio> {
+    if cfg!(target_arch = "wasm32") && window().is_some() {
+        Rc::new(WebAudio {
+            output: RefCell::new(None),
+            mixer: Cell::new(Mixer::default()),
+        })
+    } else {
+        Rc::new(NullAudio)
+    }
+}
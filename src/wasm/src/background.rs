@@ -0,0 +1,183 @@
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{CANVAS_HEIGHT, CANVAS_WIDTH, DEFAULT_COLOR, GREEN_DARK_LIGHT, LIGHT_GREEN_COLOR};
+
+const PROGRESS_SPEEDUP: f64 = 2.0; // extra scroll speed once the boss is nearly down
+const TERRAIN_STEP: f64 = 20.0; // px between terrain samples
+
+// Layer : one plane of the background, drawn back to front
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LayerKind {
+    Starfield { count: usize, size: f64 },
+    Grid { spacing: f64 },
+    Terrain { width: f64, scale: f64 }, // canyon walls, width in px, scale in px per noise step
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Layer {
+    pub kind: LayerKind,
+    pub speed: f64, // parallax: scroll px per px of camera travel
+    pub color: &'static str,
+    pub alpha: f64,
+}
+
+// Background Definition : the layers of a level and its base scroll speed
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BackgroundDef {
+    pub name: &'static str,
+    pub speed: f64, // camera travel px per tick
+    pub layers: &'static [Layer],
+}
+
+pub const LEVELS: [BackgroundDef; 1] = [BackgroundDef {
+    name: "Green canyon",
+    speed: 1.0,
+    layers: &[
+        Layer {
+            kind: LayerKind::Starfield {
+                count: 80,
+                size: 1.5,
+            },
+            speed: 0.2,
+            color: DEFAULT_COLOR,
+            alpha: 0.6,
+        },
+        Layer {
+            kind: LayerKind::Starfield {
+                count: 40,
+                size: 2.5,
+            },
+            speed: 0.5,
+            color: LIGHT_GREEN_COLOR,
+            alpha: 0.4,
+        },
+        Layer {
+            kind: LayerKind::Grid { spacing: 80.0 },
+            speed: 1.0,
+            color: DEFAULT_COLOR,
+            alpha: 0.15,
+        },
+        Layer {
+            kind: LayerKind::Terrain {
+                width: 90.0,
+                scale: 160.0,
+            },
+            speed: 2.0,
+            color: GREEN_DARK_LIGHT,
+            alpha: 1.0,
+        },
+        Layer {
+            kind: LayerKind::Terrain {
+                width: 60.0,
+                scale: 120.0,
+            },
+            speed: 3.0,
+            color: DEFAULT_COLOR,
+            alpha: 0.35,
+        },
+    ],
+}];
+
+/**
+ * a stable pseudo random number in 0.0 - 1.0 for an integer, the same on every frame
+ */
+fn hash(i: i64, seed: u64) -> f64 {
+    let mut _z = (i as u64) ^ seed.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+    _z = (_z ^ (_z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    _z = (_z ^ (_z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    _z ^= _z >> 31;
+    (_z >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/**
+ * smooth 1D value noise in 0.0 - 1.0
+ */
+fn get_noise(t: f64, seed: u64) -> f64 {
+    let _i = t.floor();
+    let _f = t - _i;
+    let _smooth = _f * _f * (3.0 - 2.0 * _f);
+    let (_a, _b) = (hash(_i as i64, seed), hash(_i as i64 + 1, seed));
+    _a + (_b - _a) * _smooth
+}
+
+// Background : the scrolling layers behind the playfield
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Background {
+    def: &'static BackgroundDef,
+    travel: f64, // camera travel px
+}
+
+impl Background {
+    pub fn new(level: usize) -> Self {
+        Background {
+            def: &LEVELS[level.min(LEVELS.len() - 1)],
+            travel: 0.0,
+        }
+    }
+
+    /**
+     * scroll one tick, faster as the stage progresses (0.0 - 1.0)
+     */
+    pub fn update(&mut self, progress: f32) {
+        self.travel += self.def.speed * (1.0 + progress as f64 * PROGRESS_SPEEDUP);
+    }
+
+    pub fn draw(&self, ctx: &CanvasRenderingContext2d) {
+        let (_width, _height) = (CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+        for (_seed, _layer) in self.def.layers.iter().enumerate() {
+            let _seed = _seed as u64;
+            let _scroll = self.travel * _layer.speed;
+            ctx.set_global_alpha(_layer.alpha);
+            ctx.set_fill_style_str(_layer.color);
+            ctx.set_stroke_style_str(_layer.color);
+            match _layer.kind {
+                LayerKind::Starfield { count, size } => {
+                    for i in 0..count as i64 {
+                        let _x = hash(i, _seed * 3) * _width;
+                        let _y = (hash(i, _seed * 3 + 1) * _height + _scroll).rem_euclid(_height);
+                        let _size = 1.0 + hash(i, _seed * 3 + 2) * size;
+                        ctx.fill_rect(_x, _y, _size, _size);
+                    }
+                }
+                LayerKind::Grid { spacing } => {
+                    ctx.set_line_width(1.0);
+                    ctx.begin_path();
+                    let mut _y = _scroll.rem_euclid(spacing) - spacing;
+                    while _y < _height {
+                        ctx.move_to(0.0, _y);
+                        ctx.line_to(_width, _y);
+                        _y += spacing;
+                    }
+                    let mut _x = (_width / 2.0).rem_euclid(spacing);
+                    while _x < _width {
+                        ctx.move_to(_x, 0.0);
+                        ctx.line_to(_x, _height);
+                        _x += spacing;
+                    }
+                    ctx.stroke();
+                }
+                LayerKind::Terrain { width, scale } => {
+                    // a wall on each side, the world position of a screen row is y - scroll
+                    for (_side, _edge) in [(0, 0.0), (1, _width)] {
+                        let _sign = if _side == 0 { 1.0 } else { -1.0 };
+                        ctx.begin_path();
+                        ctx.move_to(_edge, -TERRAIN_STEP);
+                        let mut _y = -TERRAIN_STEP;
+                        while _y <= _height + TERRAIN_STEP {
+                            let _noise = get_noise((_y - _scroll) / scale, _seed * 2 + _side);
+                            ctx.line_to(_edge + _sign * width * (0.3 + 0.7 * _noise), _y);
+                            _y += TERRAIN_STEP;
+                        }
+                        ctx.line_to(_edge, _height + TERRAIN_STEP);
+                        ctx.close_path();
+                        ctx.fill();
+                    }
+                }
+            }
+        }
+        ctx.set_global_alpha(1.0);
+    }
+}
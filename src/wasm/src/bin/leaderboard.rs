@@ -0,0 +1,188 @@
+// Leaderboard reference server
+//
+// A small single-threaded HTTP server for local play:
+//
+//   cargo run --bin leaderboard [address]
+//
+// Runs are verified by replaying them headlessly before they enter the
+// rankings. The rankings live in memory and are lost when the server stops.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use wasm::leaderboard::{
+    HighScore, HighScores, Rankings, Submission, SubmitResult, parse_difficulty, verify,
+};
+
+const ADDRESS: &str = "127.0.0.1:8787";
+const MAX_BODY_SIZE: usize = 16 * 1024 * 1024; // replays of long runs are a few MB
+const MAX_HEADER_SIZE: usize = 16 * 1024; // request line & headers
+const TIMEOUT: Duration = Duration::from_secs(10); // a stalled client holds the only thread this long
+
+struct HttpRequest {
+    method: String,
+    path: String,
+    query: String,
+    body: String,
+}
+
+fn main() {
+    let _address = std::env::args().nth(1).unwrap_or(ADDRESS.to_string());
+    let _listener = TcpListener::bind(&_address).expect("bind the leaderboard address");
+    println!("leaderboard listening on http://{}", _address);
+
+    let mut _rankings = HighScores::default();
+    for _stream in _listener.incoming() {
+        let _result = _stream.and_then(|s| handle(s, &mut _rankings));
+        if let Err(e) = _result {
+            eprintln!("connection error: {}", e);
+        }
+    }
+}
+
+/**
+ * read one request and answer it, the connection closes afterwards
+ */
+fn handle(mut stream: TcpStream, rankings: &mut HighScores) -> std::io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let Some(_request) = read_request(&stream)? else {
+        return respond(&mut stream, "400 Bad Request", &to_json(&"bad request"));
+    };
+    match (_request.method.as_str(), _request.path.as_str()) {
+        // CORS preflight from the browser
+        ("OPTIONS", _) => respond(&mut stream, "204 No Content", ""),
+        ("POST", "/runs") => {
+            // rejected runs are answered too, with the reason
+            let _result = submit(&_request.body, rankings);
+            respond(&mut stream, "200 OK", &to_json(&_result))
+        }
+        ("GET", "/rankings") => {
+            let _difficulty = _request
+                .query
+                .split('&')
+                .find_map(|p| p.strip_prefix("difficulty="))
+                .and_then(parse_difficulty);
+            let Some(_difficulty) = _difficulty else {
+                return respond(
+                    &mut stream,
+                    "400 Bad Request",
+                    &to_json(&"unknown difficulty"),
+                );
+            };
+            let _rankings = Rankings {
+                difficulty: _difficulty,
+                entries: rankings.get(_difficulty).to_vec(),
+            };
+            respond(&mut stream, "200 OK", &to_json(&_rankings))
+        }
+        _ => respond(&mut stream, "404 Not Found", &to_json(&"not found")),
+    }
+}
+
+fn submit(body: &str, rankings: &mut HighScores) -> SubmitResult {
+    let _rejected = |reason: String| SubmitResult {
+        accepted: false,
+        rank: None,
+        reason: Some(reason),
+    };
+    let _submission: Submission = match serde_json::from_str(body) {
+        Ok(_submission) => _submission,
+        Err(e) => return _rejected(format!("invalid submission: {}", e)),
+    };
+    if let Err(_reason) = verify(&_submission) {
+        println!("rejected {}: {}", _submission.name, _reason);
+        return _rejected(_reason);
+    }
+    let _entry = HighScore {
+        score: _submission.score,
+        milli_secondtime: _submission.milli_secondtime,
+        date: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0.0, |d| d.as_millis() as f64),
+        name: _submission.name.trim().to_string(),
+        seed: _submission.replay.seed,
+        difficulty: _submission.replay.difficulty,
+    };
+    println!(
+        "accepted {}: {} points, {} ms",
+        _entry.name, _entry.score, _entry.milli_secondtime
+    );
+    SubmitResult {
+        accepted: true,
+        rank: rankings.insert(_entry),
+        reason: None,
+    }
+}
+
+fn read_request(stream: &TcpStream) -> std::io::Result<Option<HttpRequest>> {
+    // nothing past the largest request is read, however long a header line is
+    let mut _reader = BufReader::new(stream.take((MAX_HEADER_SIZE + MAX_BODY_SIZE) as u64));
+    let mut _line = String::new();
+    _reader.read_line(&mut _line)?;
+    let mut _parts = _line.split_whitespace();
+    let (Some(_method), Some(_target)) = (_parts.next(), _parts.next()) else {
+        return Ok(None);
+    };
+    let (_path, _query) = _target.split_once('?').unwrap_or((_target, ""));
+    let mut _request = HttpRequest {
+        method: _method.to_string(),
+        path: _path.to_string(),
+        query: _query.to_string(),
+        body: String::new(),
+    };
+
+    // headers
+
+    let mut _length = 0;
+    let mut _header_size = _line.len();
+    loop {
+        let mut _header = String::new();
+        if _reader.read_line(&mut _header)? == 0 || _header.trim().is_empty() {
+            break;
+        }
+        _header_size += _header.len();
+        if _header_size > MAX_HEADER_SIZE {
+            return Ok(None);
+        }
+        if let Some((_name, _value)) = _header.split_once(':')
+            && _name.trim().eq_ignore_ascii_case("content-length")
+        {
+            _length = _value.trim().parse().unwrap_or(0);
+        }
+    }
+    if _length > MAX_BODY_SIZE {
+        return Ok(None);
+    }
+
+    // body
+
+    let mut _body = vec![0; _length];
+    _reader.read_exact(&mut _body)?;
+    _request.body = String::from_utf8_lossy(&_body).into_owned();
+    Ok(Some(_request))
+}
+
+fn respond(stream: &mut TcpStream, status: &str, body: &str) -> std::io::Result<()> {
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\n\
+         Content-Type: application/json\r\n\
+         Content-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\n\
+         Access-Control-Allow-Methods: GET, POST, OPTIONS\r\n\
+         Access-Control-Allow-Headers: Content-Type\r\n\
+         Connection: close\r\n\
+         \r\n\
+         {}",
+        status,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> String {
+    serde_json::to_string(value).unwrap_or_default()
+}
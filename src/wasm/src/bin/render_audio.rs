@@ -0,0 +1,36 @@
+// Offline audio renderer
+//
+// Plays a replay headlessly and writes its sound effects and music to a WAV file:
+//
+//   cargo run --bin render_audio <replay.json> <output.wav>
+//
+// The JSON is either a replay or a leaderboard submission carrying one.
+
+use std::fs::File;
+use std::io::BufWriter;
+
+use wasm::leaderboard::Submission;
+use wasm::offline_audio::{Replay, SAMPLE_RATE, render_replay, write_wav};
+
+fn main() {
+    let _args: Vec<String> = std::env::args().collect();
+    let [_, _input, _output] = &_args[..] else {
+        eprintln!("usage: render_audio <replay.json> <output.wav>");
+        std::process::exit(2);
+    };
+    let _json = std::fs::read_to_string(_input).expect("read the replay");
+    let _replay: Replay = serde_json::from_str::<Submission>(&_json)
+        .map(|s| s.replay)
+        .or_else(|_| serde_json::from_str(&_json))
+        .expect("parse the replay");
+
+    let _samples = render_replay(&_replay, SAMPLE_RATE);
+    let mut _writer = BufWriter::new(File::create(_output).expect("create the WAV file"));
+    write_wav(&mut _writer, &_samples, SAMPLE_RATE).expect("write the WAV file");
+    println!(
+        "{}: {:.1} s. at {} Hz",
+        _output,
+        _samples.len() as f64 / SAMPLE_RATE as f64,
+        SAMPLE_RATE
+    );
+}
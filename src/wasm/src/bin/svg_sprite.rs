@@ -0,0 +1,49 @@
+// SVG sprite importer
+//
+// Converts a simple SVG drawing into the sprite text format of src/sprites/:
+//
+//   cargo run --bin svg_sprite <drawing.svg> [sprite.txt]
+//
+// Writes to the standard output without an output file. The colors of the
+// drawing are listed as palette slots in the header, for the colors of a Palette.
+
+use std::path::Path;
+
+use wasm::svg::import;
+
+fn main() {
+    let _args: Vec<String> = std::env::args().collect();
+    let (_input, _output) = match &_args[..] {
+        [_, _input] => (_input, None),
+        [_, _input, _output] => (_input, Some(_output)),
+        _ => {
+            eprintln!("usage: svg_sprite <drawing.svg> [sprite.txt]");
+            std::process::exit(2);
+        }
+    };
+    let _svg = std::fs::read_to_string(_input).expect("read the SVG file");
+    let (_sprite, _palette) = import(&_svg).unwrap_or_else(|e| {
+        eprintln!("{}: {}", _input, e);
+        std::process::exit(1);
+    });
+
+    let _name = Path::new(_input)
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    let _slots: Vec<String> = _palette
+        .iter()
+        .enumerate()
+        .map(|(i, c)| format!("{} {}", i, c))
+        .collect();
+    let _text = format!(
+        "; Imported from {}\n;\n; slots: {}\n\n{}",
+        _name,
+        _slots.join(", "),
+        _sprite
+    );
+    match _output {
+        Some(_output) => std::fs::write(_output, _text).expect("write the sprite file"),
+        None => print!("{}", _text),
+    }
+}
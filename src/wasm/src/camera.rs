@@ -0,0 +1,148 @@
+use wasm_bindgen::JsCast;
+use web_sys::{
+    CanvasRenderingContext2d, HtmlCanvasElement, OffscreenCanvas, OffscreenCanvasRenderingContext2d,
+};
+
+use crate::sprite::{Palette, SpriteId};
+use crate::{CANVAS_HEIGHT, CANVAS_WIDTH, GREEN_DARK_LIGHT};
+
+const SHAKE_DECAY: f64 = 0.88; // shake kept per tick
+const SHAKE_MIN: f64 = 0.5; // px, below this the shake stops
+const BOSS_FLASH_TIME: i32 = 2; // ticks the boss stays white
+const BOSS_FLASH_INTERVAL: i32 = 8; // ticks between flashes, constant fire doesn't keep it white
+const BOSS_FLASH_ALPHA: f64 = 0.8; // of the white drawn over the boss
+const SLOW_MOTION_RATE: i32 = 3; // the world steps once every this many ticks
+const FADE_TIME: i32 = 20; // ticks of a fade out, and of the fade in after it
+
+// Camera : effects over the drawn game, driven by world events
+//
+// The world doesn't know about them, so they never change a run or its replay.
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Camera {
+    tick: i32,
+    shake: f64,      // px
+    boss_flash: i32, // ticks until the boss can flash again
+    slow_tick: i32,
+    fade: i32,                         // ticks left of the fade in
+    fade_out: i32,                     // ticks left of the fade out, the world waits for it
+    snapshot: Option<OffscreenCanvas>, // the last frame of the stage fading out
+}
+
+impl Camera {
+    pub fn update(&mut self) {
+        self.tick += 1;
+        self.shake *= SHAKE_DECAY;
+        if self.shake < SHAKE_MIN {
+            self.shake = 0.0;
+        }
+        self.boss_flash = (self.boss_flash - 1).max(0);
+        if self.fade_out > 0 {
+            self.fade_out -= 1;
+            if self.fade_out == 0 {
+                self.fade = FADE_TIME;
+            }
+        } else {
+            self.fade = (self.fade - 1).max(0);
+        }
+    }
+
+    // Screen Shake
+
+    pub fn shake(&mut self, amount: f64) {
+        self.shake = self.shake.max(amount);
+    }
+    /**
+     * move the playfield by the shake, call end() after drawing it
+     */
+    pub fn begin(&self, ctx: &CanvasRenderingContext2d) {
+        ctx.save();
+        if self.shake > 0.0 {
+            let _tick = self.tick as f64;
+            let _ = ctx.translate(
+                self.shake * (_tick * 1.7).sin(),
+                self.shake * (_tick * 2.3).cos(),
+            );
+        }
+    }
+    pub fn end(&self, ctx: &CanvasRenderingContext2d) {
+        ctx.restore();
+    }
+
+    // Boss Flash
+
+    pub fn flash_boss(&mut self) {
+        if self.boss_flash == 0 {
+            self.boss_flash = BOSS_FLASH_INTERVAL;
+        }
+    }
+    /**
+     * a white copy of the boss over it while it flashes
+     */
+    pub fn draw_boss_flash(
+        &self,
+        ctx: &CanvasRenderingContext2d,
+        sprite: SpriteId,
+        x: f32,
+        y: f32,
+    ) {
+        if self.boss_flash <= BOSS_FLASH_INTERVAL - BOSS_FLASH_TIME {
+            return;
+        }
+        ctx.set_global_alpha(BOSS_FLASH_ALPHA);
+        sprite.draw(ctx, x, y, Palette::Flash);
+        ctx.set_global_alpha(1.0);
+    }
+
+    // Slow Motion
+
+    /**
+     * whether the world steps this tick in slow motion
+     */
+    pub fn is_slow_tick(&mut self) -> bool {
+        self.slow_tick = (self.slow_tick + 1) % SLOW_MOTION_RATE;
+        self.slow_tick == 0
+    }
+
+    // Fade
+
+    /**
+     * fade out the frame on the canvas, the last one of the old stage, then fade in the new stage
+     */
+    pub fn fade_out(&mut self, canvas: &HtmlCanvasElement) {
+        if self.snapshot.is_none() {
+            self.snapshot = OffscreenCanvas::new(canvas.width(), canvas.height()).ok();
+        }
+        let _context = self
+            .snapshot
+            .as_ref()
+            .and_then(|s| s.get_context("2d").ok()?)
+            .and_then(|c| c.dyn_into::<OffscreenCanvasRenderingContext2d>().ok());
+        match _context {
+            Some(_context) => {
+                let _ = _context.draw_image_with_html_canvas_element(canvas, 0.0, 0.0);
+                self.fade_out = FADE_TIME;
+            }
+            // no snapshot where OffscreenCanvas isn't supported
+            None => self.fade = FADE_TIME,
+        }
+    }
+    pub fn is_fading_out(&self) -> bool {
+        self.fade_out > 0
+    }
+    pub fn draw_fade(&self, ctx: &CanvasRenderingContext2d) {
+        if self.fade_out > 0
+            && let Some(_snapshot) = &self.snapshot
+        {
+            let _ = ctx.draw_image_with_offscreen_canvas(_snapshot, 0.0, 0.0);
+            ctx.set_global_alpha(1.0 - self.fade_out as f64 / FADE_TIME as f64);
+        } else if self.fade > 0 {
+            ctx.set_global_alpha(self.fade as f64 / FADE_TIME as f64);
+        } else {
+            return;
+        }
+        ctx.set_fill_style_str(GREEN_DARK_LIGHT);
+        ctx.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+        ctx.set_global_alpha(1.0);
+    }
+}
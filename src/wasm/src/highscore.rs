@@ -0,0 +1,96 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Difficulty;
+use crate::storage::Storage;
+
+const HIGHSCORE_STORAGE_KEY: &str = "barrage.highscores";
+pub const HIGHSCORE_ENTRIES: usize = 10; // entries kept per difficulty
+pub const NAME_LENGTH: usize = 8;
+const DEFAULT_NAME: &str = "PLAYER";
+
+// High Score : one cleared run
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct HighScore {
+    pub score: i32,
+    pub milli_secondtime: i32, // clear time from the game clock
+    pub date: f64,             // ms since the epoch
+    pub name: String,
+    pub seed: u64, // World seed of the run
+    #[serde(default)]
+    pub difficulty: Difficulty, // older saves lack it, taken from their table on load
+}
+
+// High Scores : the top HIGHSCORE_ENTRIES per difficulty, best first
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct HighScores {
+    tables: Vec<(Difficulty, Vec<HighScore>)>,
+    last_name: String, // name entry starts from the last name used
+}
+
+impl HighScores {
+    pub fn load(storage: &dyn Storage) -> Self {
+        let mut _high_scores: HighScores = storage
+            .load(HIGHSCORE_STORAGE_KEY)
+            .and_then(|_json| serde_json::from_str(&_json).ok())
+            .unwrap_or_default();
+        for (_difficulty, _table) in _high_scores.tables.iter_mut() {
+            _table.iter_mut().for_each(|e| e.difficulty = *_difficulty);
+        }
+        _high_scores
+    }
+    pub fn save(&self, storage: &dyn Storage) {
+        if let Ok(_json) = serde_json::to_string(self) {
+            storage.save(HIGHSCORE_STORAGE_KEY, &_json);
+        }
+    }
+    pub fn get(&self, difficulty: Difficulty) -> &[HighScore] {
+        self.tables
+            .iter()
+            .find(|(d, _)| *d == difficulty)
+            .map_or(&[], |(_, _table)| _table)
+    }
+    pub fn get_last_name(&self) -> &str {
+        if self.last_name.is_empty() {
+            DEFAULT_NAME
+        } else {
+            &self.last_name
+        }
+    }
+    // higher score first, faster clear on a tie
+    fn get_rank(&self, entry: &HighScore) -> usize {
+        self.get(entry.difficulty)
+            .iter()
+            .take_while(|e| {
+                e.score > entry.score
+                    || (e.score == entry.score && e.milli_secondtime <= entry.milli_secondtime)
+            })
+            .count()
+    }
+    pub fn qualifies(&self, entry: &HighScore) -> bool {
+        self.get_rank(entry) < HIGHSCORE_ENTRIES
+    }
+    /**
+     * insert a run in the table of its difficulty, returns its rank (0 = best) or None if it doesn't make the table
+     */
+    pub fn insert(&mut self, entry: HighScore) -> Option<usize> {
+        let _difficulty = entry.difficulty;
+        let _rank = self.get_rank(&entry);
+        if _rank >= HIGHSCORE_ENTRIES {
+            return None;
+        }
+        self.last_name = entry.name.clone();
+        if !self.tables.iter().any(|(d, _)| *d == _difficulty) {
+            self.tables.push((_difficulty, vec![]));
+        }
+        let (_, _table) = self
+            .tables
+            .iter_mut()
+            .find(|(d, _)| *d == _difficulty)
+            .unwrap();
+        _table.insert(_rank, entry);
+        _table.truncate(HIGHSCORE_ENTRIES);
+        Some(_rank)
+    }
+}
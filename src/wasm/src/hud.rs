@@ -0,0 +1,222 @@
+use web_sys::CanvasRenderingContext2d;
+
+use crate::world::{SuperMode, World};
+use crate::{
+    BOSS_NAME, CANVAS_WIDTH, DEFAULT_COLOR, FPS, GREEN_DARK_LIGHT, LIGHT_GREEN_COLOR,
+    LIGHT_YELLOR_GREEN, get_passed_time,
+};
+
+const BOSS_PHASES: i32 = 3; // the boss health bar is split in this many phases
+
+// Layout : where every HUD element is, (x, y) of the text baseline or the top left corner
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Rect {
+    x: f64,
+    y: f64,
+    w: f64,
+    h: f64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Layout {
+    boss_bar: Rect,
+    boss_name: (f64, f64),
+    phase_timer: (f64, f64), // right aligned
+    time: (f64, f64),
+    bullets: (f64, f64),
+    weapon: (f64, f64),
+    difficulty: (f64, f64),
+    super_gauge: Rect,
+    super_label: (f64, f64),
+    graze: (f64, f64),
+    score: (f64, f64),
+    super_state: (f64, f64),
+}
+
+const LAYOUT: Layout = Layout {
+    boss_bar: Rect {
+        x: 30.0,
+        y: 14.0,
+        w: CANVAS_WIDTH as f64 - 60.0,
+        h: 14.0,
+    },
+    boss_name: (30.0, 48.0),
+    phase_timer: (CANVAS_WIDTH as f64 - 30.0, 48.0),
+    time: (30.0, 90.0),
+    bullets: (30.0, 120.0),
+    weapon: (30.0, 145.0),
+    difficulty: (30.0, 170.0),
+    super_gauge: Rect {
+        x: 30.0,
+        y: 185.0,
+        w: 200.0,
+        h: 16.0,
+    },
+    super_label: (30.0, 222.0),
+    graze: (30.0, 250.0),
+    score: (30.0, 275.0),
+    super_state: (30.0, 300.0),
+};
+
+// Hud : the boss health bar & the player status over the playfield
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Hud {
+    phase: i32,
+    phase_tick: i32, // world tick the current phase began
+}
+
+impl Hud {
+    /**
+     * follow the boss phases, call every tick the world steps
+     */
+    pub fn update(&mut self, world: &World) {
+        let _phase = get_phase(world);
+        if _phase != self.phase || world.tick < self.phase_tick {
+            self.phase = _phase;
+            self.phase_tick = world.tick;
+        }
+    }
+
+    /**
+     * bomb_key: the key bound to Bomb, shown when super mode is ready
+     */
+    pub fn draw(&self, ctx: &CanvasRenderingContext2d, world: &World, bomb_key: &str) {
+        self.draw_boss(ctx, world);
+        draw_status(ctx, world, bomb_key);
+    }
+
+    fn draw_boss(&self, ctx: &CanvasRenderingContext2d, world: &World) {
+        let (_hp, _max_hp) = world.bosses.iter().fold((0, 0), |(_hp, _max_hp), b| {
+            (_hp + b.hp.max(0), _max_hp + b.max_hp)
+        });
+        if _max_hp == 0 || world.boss_explosion.is_some() {
+            return;
+        }
+        let _bar = LAYOUT.boss_bar;
+        let _phase_w = _bar.w / BOSS_PHASES as f64;
+
+        // Draw health, the current phase brighter than the ones left
+
+        let _hp_w = _bar.w * _hp as f64 / _max_hp as f64;
+        ctx.set_fill_style_str(GREEN_DARK_LIGHT);
+        ctx.fill_rect(_bar.x, _bar.y, _bar.w, _bar.h);
+        let _current_x = _bar.w - _phase_w * (self.phase + 1) as f64;
+        ctx.set_fill_style_str(DEFAULT_COLOR);
+        ctx.fill_rect(_bar.x, _bar.y, _hp_w.min(_current_x), _bar.h);
+        ctx.set_fill_style_str(LIGHT_YELLOR_GREEN);
+        ctx.fill_rect(
+            _bar.x + _current_x,
+            _bar.y,
+            (_hp_w - _current_x).max(0.0),
+            _bar.h,
+        );
+
+        // Draw phase segments
+
+        ctx.set_stroke_style_str(LIGHT_GREEN_COLOR);
+        ctx.set_line_width(2.0);
+        ctx.stroke_rect(_bar.x, _bar.y, _bar.w, _bar.h);
+        ctx.begin_path();
+        for i in 1..BOSS_PHASES {
+            let _x = _bar.x + _phase_w * i as f64;
+            ctx.move_to(_x, _bar.y);
+            ctx.line_to(_x, _bar.y + _bar.h);
+        }
+        ctx.stroke();
+
+        // Draw name & phase timer
+
+        ctx.set_font("18px myfont");
+        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
+        let _ = ctx.fill_text(BOSS_NAME, LAYOUT.boss_name.0, LAYOUT.boss_name.1);
+        let _phase_time = (world.tick - self.phase_tick) * FPS;
+        let _txt = format!(
+            "Phase {}/{}  {}",
+            self.phase + 1,
+            BOSS_PHASES,
+            get_passed_time(&_phase_time)
+        );
+        ctx.set_text_align("right");
+        let _ = ctx.fill_text(&_txt, LAYOUT.phase_timer.0, LAYOUT.phase_timer.1);
+        ctx.set_text_align("left");
+    }
+}
+
+/**
+ * phase of the boss fight, 0 - BOSS_PHASES - 1 from the damage dealt
+ */
+fn get_phase(world: &World) -> i32 {
+    ((world.get_progress() * BOSS_PHASES as f32) as i32).clamp(0, BOSS_PHASES - 1)
+}
+
+fn draw_status(ctx: &CanvasRenderingContext2d, world: &World, bomb_key: &str) {
+    // Draw Time
+
+    ctx.set_font("28px myfont");
+    ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
+    let _str = get_passed_time(&world.get_milli_secondtime());
+    let _ = ctx.fill_text(&_str, LAYOUT.time.0, LAYOUT.time.1);
+
+    // Draw Number of Bullets, Weapon, Difficulty & Rank
+
+    ctx.set_font("18px myfont");
+    let _txt = format!("Bullets: {}", world.bullets.len());
+    let _ = ctx.fill_text(&_txt, LAYOUT.bullets.0, LAYOUT.bullets.1);
+    let _txt = format!("Weapon: {}", world.weapon.name());
+    let _ = ctx.fill_text(&_txt, LAYOUT.weapon.0, LAYOUT.weapon.1);
+    let _difficulty = if world.rank_enabled {
+        format!(
+            "{} Rank: {:.0}%",
+            world.difficulty.name(),
+            world.rank * 100.0
+        )
+    } else {
+        world.difficulty.name().to_string()
+    };
+    let _ = ctx.fill_text(&_difficulty, LAYOUT.difficulty.0, LAYOUT.difficulty.1);
+
+    // Draw Super Gauge
+
+    let _gauge = LAYOUT.super_gauge;
+    let _ratio: f64 = world.super_mode.get_ratio().into();
+    ctx.set_stroke_style_str(DEFAULT_COLOR);
+    ctx.set_line_width(2.0);
+    ctx.stroke_rect(_gauge.x, _gauge.y, _gauge.w, _gauge.h);
+    ctx.set_fill_style_str(if world.super_mode.is_active() {
+        LIGHT_YELLOR_GREEN
+    } else {
+        DEFAULT_COLOR
+    });
+    ctx.fill_rect(_gauge.x, _gauge.y, _gauge.w * _ratio, _gauge.h);
+    ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
+    let (_x, _y) = LAYOUT.super_label;
+    match world.super_mode {
+        SuperMode::Ready if world.tick / 20 % 2 == 0 => {
+            let _txt = format!("SUPER READY: {} / Middle Click", bomb_key);
+            let _ = ctx.fill_text(&_txt, _x, _y);
+        }
+        SuperMode::Active(_) => {
+            let _ = ctx.fill_text("SUPER", _x, _y);
+        }
+        _ => {}
+    }
+
+    // Draw Graze & Score
+
+    let _txt = format!("Graze: {}", world.graze);
+    let _ = ctx.fill_text(&_txt, LAYOUT.graze.0, LAYOUT.graze.1);
+    let _txt = format!("Score: {}", world.score);
+    let _ = ctx.fill_text(&_txt, LAYOUT.score.0, LAYOUT.score.1);
+
+    // Draw Super State : the ship has a single life and no bomb stock, the super is its bomb
+
+    let _state = match world.super_mode {
+        SuperMode::Charging(_) => format!("{}%", (world.super_mode.get_ratio() * 100.0) as i32),
+        SuperMode::Ready => "READY".to_string(),
+        SuperMode::Active(_) => "ACTIVE".to_string(),
+    };
+    let _txt = format!("Super: {}", _state);
+    let _ = ctx.fill_text(&_txt, LAYOUT.super_state.0, LAYOUT.super_state.1);
+}
@@ -0,0 +1,583 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use wasm_bindgen::JsCast;
+use web_sys::{Gamepad, GamepadButton, window};
+
+use crate::settings::ControlScheme;
+use crate::storage::Storage;
+
+const KEYMAP_STORAGE_KEY: &str = "barrage.keymap";
+const GAMEPAD_DEADZONE: f32 = 0.2; // radial deadzone of the analog stick
+const TAP_TIME: f64 = 250.0; // max ms between touch down and up for a tap
+const TAP_DISTANCE: f32 = 12.0; // max movement for a tap
+const HOLD_TICKS: i32 = 40; // ticks of holding two fingers for TwoFingerHold
+
+// Action : what the player wants to do, independent from the key
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Left,
+    Right,
+    Up,
+    Down,
+    Shoot,
+    Focus,
+    Bomb,
+    Pause,
+    Weapon,
+}
+
+impl Action {
+    pub const ALL: [Action; 9] = [
+        Action::Left,
+        Action::Right,
+        Action::Up,
+        Action::Down,
+        Action::Shoot,
+        Action::Focus,
+        Action::Bomb,
+        Action::Pause,
+        Action::Weapon,
+    ];
+    pub fn name(&self) -> &str {
+        match self {
+            Action::Left => "Left",
+            Action::Right => "Right",
+            Action::Up => "Up",
+            Action::Down => "Down",
+            Action::Shoot => "Shoot",
+            Action::Focus => "Focus",
+            Action::Bomb => "Bomb",
+            Action::Pause => "Pause",
+            Action::Weapon => "Weapon",
+        }
+    }
+    fn bit(&self) -> u16 {
+        1 << (*self as u16)
+    }
+}
+
+// Input State : everything the simulation reads for one tick
+//
+// All devices write into it, the simulation reads nothing else, so a recorded
+// sequence of input states replays a game and a bot or a test can play without DOM events.
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct InputState {
+    pub axis_x: f32, // movement -1.0 - 1.0 from keys, D-pad or analog stick
+    pub axis_y: f32,
+    pub drag_x: f32, // relative touch drag
+    pub drag_y: f32,
+    pub pointer_x: Option<f32>, // mouse position if it moved on this tick: the ship steers toward it
+    pub click: Option<(f32, f32)>, // click or tap: toggles firing, selects menus
+    pub held: u16,              // Action bits
+    pub pressed: u16,           // Action bits, pressed on this tick
+}
+
+impl InputState {
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held & action.bit() != 0
+    }
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed & action.bit() != 0
+    }
+    pub fn hold(&mut self, action: Action) {
+        self.held |= action.bit();
+    }
+    pub fn press(&mut self, action: Action) {
+        self.pressed |= action.bit();
+    }
+    /**
+     * add what happened on a tick the world didn't step: presses, a click & the drag
+     */
+    pub fn merge(&mut self, skipped: &InputState) {
+        self.pressed |= skipped.pressed;
+        self.click = self.click.or(skipped.click);
+        self.pointer_x = self.pointer_x.or(skipped.pointer_x);
+        self.drag_x += skipped.drag_x;
+        self.drag_y += skipped.drag_y;
+    }
+}
+
+// Key Map : action -> KeyboardEvent.code
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct KeyMap {
+    bindings: Vec<(Action, String)>,
+}
+
+impl Default for KeyMap {
+    fn default() -> Self {
+        KeyMap {
+            bindings: vec![
+                (Action::Left, "ArrowLeft".to_string()),
+                (Action::Right, "ArrowRight".to_string()),
+                (Action::Up, "ArrowUp".to_string()),
+                (Action::Down, "ArrowDown".to_string()),
+                (Action::Shoot, "KeyZ".to_string()),
+                (Action::Focus, "ShiftLeft".to_string()),
+                (Action::Bomb, "KeyX".to_string()),
+                (Action::Pause, "Escape".to_string()),
+                (Action::Weapon, "KeyC".to_string()),
+            ],
+        }
+    }
+}
+
+impl KeyMap {
+    /**
+     * load the key map saved by the key config screen, or the default one
+     */
+    pub fn load(storage: &dyn Storage) -> Self {
+        storage
+            .load(KEYMAP_STORAGE_KEY)
+            .and_then(|_json| serde_json::from_str(&_json).ok())
+            .unwrap_or_default()
+    }
+    pub fn save(&self, storage: &dyn Storage) {
+        if let Ok(_json) = serde_json::to_string(self) {
+            storage.save(KEYMAP_STORAGE_KEY, &_json);
+        }
+    }
+    pub fn get_code(&self, action: Action) -> &str {
+        self.bindings
+            .iter()
+            .find(|(_action, _)| *_action == action)
+            .map(|(_, _code)| _code.as_str())
+            .unwrap_or("")
+    }
+    pub fn get_action(&self, code: &str) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, _code)| _code == code)
+            .map(|(_action, _)| *_action)
+    }
+    /**
+     * bind the key to the action, the action which had the key before gets the old key
+     */
+    pub fn bind(&mut self, action: Action, code: &str) {
+        let _old = self.get_code(action).to_string();
+        for (_action, _code) in self.bindings.iter_mut() {
+            if *_action == action {
+                *_code = code.to_string();
+            } else if _code == code {
+                *_code = _old.clone();
+            }
+        }
+        if self.get_action(code).is_none() {
+            self.bindings.push((action, code.to_string()));
+        }
+    }
+}
+
+// Keyboard : key state between animation frames
+//
+// keydown / keyup events arrive at any time, the fixed-timestep update reads
+// held and pressed actions every tick, pressed actions are cleared by end_tick.
+
+#[derive(Clone, Debug, Default)]
+pub struct Keyboard {
+    pub key_map: KeyMap,
+    held: HashSet<Action>,
+    pressed: HashSet<Action>,
+}
+
+impl Keyboard {
+    pub fn new(key_map: KeyMap) -> Self {
+        Keyboard {
+            key_map,
+            held: HashSet::new(),
+            pressed: HashSet::new(),
+        }
+    }
+    // returns true when the key is bound to an action
+    pub fn key_down(&mut self, code: &str) -> bool {
+        let Some(_action) = self.key_map.get_action(code) else {
+            return false;
+        };
+        // ignore key repeat
+        if self.held.insert(_action) {
+            self.pressed.insert(_action);
+        }
+        true
+    }
+    pub fn key_up(&mut self, code: &str) -> bool {
+        let Some(_action) = self.key_map.get_action(code) else {
+            return false;
+        };
+        self.held.remove(&_action);
+        true
+    }
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+    // pressed since the last tick
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+    pub fn end_tick(&mut self) {
+        self.pressed.clear();
+    }
+    // -1.0 / 0.0 / 1.0 for the pair of actions
+    pub fn get_axis(&self, negative: Action, positive: Action) -> f32 {
+        (self.is_held(positive) as i32 - self.is_held(negative) as i32) as f32
+    }
+    // release everything, e.g. when the canvas loses focus
+    pub fn clear(&mut self) {
+        self.held.clear();
+        self.pressed.clear();
+    }
+}
+
+// Gamepad Map : action -> button index of the standard gamepad mapping
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct GamepadMap {
+    bindings: Vec<(Action, u32)>,
+}
+
+impl Default for GamepadMap {
+    fn default() -> Self {
+        GamepadMap {
+            bindings: vec![
+                (Action::Shoot, 0),  // A / Cross
+                (Action::Bomb, 1),   // B / Circle
+                (Action::Focus, 4),  // LB / L1
+                (Action::Focus, 5),  // RB / R1
+                (Action::Pause, 9),  // Start / Options
+                (Action::Weapon, 2), // X / Square
+                (Action::Up, 12),    // D-pad
+                (Action::Down, 13),
+                (Action::Left, 14),
+                (Action::Right, 15),
+            ],
+        }
+    }
+}
+
+// Gamepad : polled once per tick, hot-plugged by gamepadconnected / gamepaddisconnected
+
+#[derive(Clone, Debug, Default)]
+pub struct GamepadInput {
+    pub map: GamepadMap,
+    index: Option<u32>,
+    axis_x: f32,
+    axis_y: f32,
+    held: HashSet<Action>,
+    pressed: HashSet<Action>,
+}
+
+impl GamepadInput {
+    pub fn connect(&mut self, index: u32) {
+        if self.index.is_none() {
+            self.index = Some(index);
+        }
+    }
+    pub fn disconnect(&mut self, index: u32) {
+        if self.index == Some(index) {
+            self.index = None;
+            self.release();
+        }
+    }
+    // let go of every button and center the stick
+    fn release(&mut self) {
+        self.axis_x = 0.0;
+        self.axis_y = 0.0;
+        self.held.clear();
+        self.pressed.clear();
+    }
+    pub fn is_connected(&self) -> bool {
+        self.index.is_some()
+    }
+    /**
+     * read the gamepad state, pressed holds the buttons pushed since the last poll
+     */
+    pub fn poll(&mut self) {
+        let Some(_gamepad) = self.get_gamepad() else {
+            self.release();
+            return;
+        };
+        self.index = Some(_gamepad.index());
+
+        let _buttons = _gamepad.buttons();
+        let mut _held = HashSet::new();
+        for (_action, _button) in self.map.bindings.iter() {
+            let _pressed = _buttons
+                .get(*_button)
+                .dyn_into::<GamepadButton>()
+                .map(|b| b.pressed())
+                .unwrap_or(false);
+            if _pressed {
+                _held.insert(*_action);
+            }
+        }
+        self.pressed = _held.difference(&self.held).copied().collect();
+        self.held = _held;
+
+        // left stick with a radial deadzone, rescaled to 0.0 - 1.0 outside of it
+        let _axes = _gamepad.axes();
+        let _x = _axes.get(0).as_f64().unwrap_or(0.0) as f32;
+        let _y = _axes.get(1).as_f64().unwrap_or(0.0) as f32;
+        let _length = _x.hypot(_y);
+        if _length < GAMEPAD_DEADZONE {
+            self.axis_x = 0.0;
+            self.axis_y = 0.0;
+        } else {
+            let _scale =
+                ((_length - GAMEPAD_DEADZONE) / (1.0 - GAMEPAD_DEADZONE)).min(1.0) / _length;
+            self.axis_x = _x * _scale;
+            self.axis_y = _y * _scale;
+        }
+    }
+    // the connected gamepad, or the first one found (some browsers fire no event until a button is pushed)
+    fn get_gamepad(&self) -> Option<Gamepad> {
+        let _gamepads = window()?.navigator().get_gamepads().ok()?;
+        let _gamepads = _gamepads
+            .iter()
+            .filter_map(|g| g.dyn_into::<Gamepad>().ok())
+            .filter(|g| g.connected());
+        match self.index {
+            Some(_index) => _gamepads.into_iter().find(|g| g.index() == _index),
+            None => _gamepads.into_iter().next(),
+        }
+    }
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held.contains(&action)
+    }
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+    // analog stick, the D-pad wins when pushed
+    pub fn get_axis(&self) -> (f32, f32) {
+        let _dpad_x =
+            (self.is_held(Action::Right) as i32 - self.is_held(Action::Left) as i32) as f32;
+        let _dpad_y = (self.is_held(Action::Down) as i32 - self.is_held(Action::Up) as i32) as f32;
+        if _dpad_x != 0.0 || _dpad_y != 0.0 {
+            (_dpad_x, _dpad_y)
+        } else {
+            (self.axis_x, self.axis_y)
+        }
+    }
+}
+
+// Pointer : mouse, pen and touch from pointer events, in canvas coordinates
+
+#[derive(Clone, Debug)]
+pub struct PointerInput {
+    pub id: i32,
+    pub touch: bool,
+    pub button: i16,
+    pub x: f32,
+    pub y: f32,
+    pub time: f64, // event timeStamp (ms)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Gesture {
+    Drag(f32, f32), // one finger moved by (dx, dy)
+    Tap(f32, f32),  // one finger tapped at (x, y)
+    TwoFingerTap,
+    TwoFingerHold,
+}
+
+#[derive(Clone, Debug)]
+struct TouchPoint {
+    id: i32,
+    start_x: f32,
+    start_y: f32,
+    x: f32,
+    y: f32,
+    start_time: f64,
+}
+
+// Touch Gestures : recognizes taps, drags and two finger gestures
+//
+// A gesture lasts from the first finger down to the last finger up, taps are
+// recognized on release when the fingers neither moved nor stayed long.
+
+#[derive(Clone, Debug, Default)]
+pub struct TouchGestures {
+    points: Vec<TouchPoint>,
+    max_points: usize, // fingers used in this gesture
+    moved: bool,
+    hold_ticks: i32,
+    hold_fired: bool,
+}
+
+impl TouchGestures {
+    pub fn down(&mut self, p: &PointerInput) {
+        if self.points.is_empty() {
+            self.max_points = 0;
+            self.moved = false;
+            self.hold_ticks = 0;
+            self.hold_fired = false;
+        }
+        self.points.push(TouchPoint {
+            id: p.id,
+            start_x: p.x,
+            start_y: p.y,
+            x: p.x,
+            y: p.y,
+            start_time: p.time,
+        });
+        self.max_points = self.max_points.max(self.points.len());
+    }
+    pub fn moved(&mut self, p: &PointerInput) -> Option<Gesture> {
+        let _point = self.points.iter_mut().find(|t| t.id == p.id)?;
+        let (_dx, _dy) = (p.x - _point.x, p.y - _point.y);
+        _point.x = p.x;
+        _point.y = p.y;
+        if (_point.x - _point.start_x).hypot(_point.y - _point.start_y) > TAP_DISTANCE {
+            self.moved = true;
+        }
+        // steering only with a single finger
+        if self.max_points == 1 {
+            Some(Gesture::Drag(_dx, _dy))
+        } else {
+            None
+        }
+    }
+    pub fn up(&mut self, p: &PointerInput) -> Option<Gesture> {
+        let _index = self.points.iter().position(|t| t.id == p.id)?;
+        let _point = self.points.remove(_index);
+        if !self.points.is_empty() || self.moved || self.hold_fired {
+            return None;
+        }
+        if p.time - _point.start_time > TAP_TIME {
+            return None;
+        }
+        match self.max_points {
+            1 => Some(Gesture::Tap(_point.start_x, _point.start_y)),
+            2 => Some(Gesture::TwoFingerTap),
+            _ => None,
+        }
+    }
+    pub fn cancel(&mut self, p: &PointerInput) {
+        self.points.retain(|t| t.id != p.id);
+        self.moved = true;
+    }
+    pub fn is_touching(&self) -> bool {
+        !self.points.is_empty()
+    }
+    /**
+     * called every tick, recognizes two fingers held still for HOLD_TICKS
+     */
+    pub fn tick(&mut self) -> Option<Gesture> {
+        if self.points.len() != 2 || self.moved || self.hold_fired {
+            return None;
+        }
+        self.hold_ticks += 1;
+        if self.hold_ticks >= HOLD_TICKS {
+            self.hold_fired = true;
+            return Some(Gesture::TwoFingerHold);
+        }
+        None
+    }
+}
+
+// Input Devices : keyboard, gamepad, mouse and touch sampled into one InputState per tick
+
+#[derive(Clone, Debug, Default)]
+pub struct InputDevices {
+    pub keyboard: Keyboard,
+    pub gamepad: GamepadInput,
+    touch: TouchGestures,
+    mouse_down: bool,    // left button held
+    pending: InputState, // mouse and touch input since the last sample
+}
+
+impl InputDevices {
+    pub fn new(key_map: KeyMap) -> Self {
+        InputDevices {
+            keyboard: Keyboard::new(key_map),
+            ..Default::default()
+        }
+    }
+    pub fn key_down(&mut self, code: &str) -> bool {
+        self.keyboard.key_down(code)
+    }
+    pub fn key_up(&mut self, code: &str) -> bool {
+        self.keyboard.key_up(code)
+    }
+    pub fn blur(&mut self) {
+        self.keyboard.clear();
+        self.mouse_down = false;
+    }
+    // mouse buttons act at once, touches wait for the gesture
+    pub fn pointer_down(&mut self, p: &PointerInput) {
+        if p.touch {
+            self.touch.down(p);
+            return;
+        }
+        match p.button {
+            2 => self.pending.press(Action::Weapon),
+            1 => self.pending.press(Action::Bomb),
+            _ => {
+                self.mouse_down = true;
+                self.pending.click = Some((p.x, p.y));
+            }
+        }
+    }
+    pub fn pointer_move(&mut self, p: &PointerInput) {
+        if !p.touch {
+            self.pending.pointer_x = Some(p.x);
+            return;
+        }
+        if let Some(Gesture::Drag(_dx, _dy)) = self.touch.moved(p) {
+            self.pending.drag_x += _dx;
+            self.pending.drag_y += _dy;
+        }
+    }
+    pub fn pointer_up(&mut self, p: &PointerInput) {
+        if !p.touch {
+            if p.button == 0 {
+                self.mouse_down = false;
+            }
+            return;
+        }
+        match self.touch.up(p) {
+            Some(Gesture::Tap(_x, _y)) => self.pending.click = Some((_x, _y)),
+            Some(Gesture::TwoFingerTap) => self.pending.press(Action::Bomb),
+            _ => {}
+        }
+    }
+    pub fn pointer_cancel(&mut self, p: &PointerInput) {
+        if p.touch {
+            self.touch.cancel(p);
+        } else {
+            self.mouse_down = false;
+        }
+    }
+    /**
+     * called once per tick: poll the gamepad and merge every device
+     */
+    pub fn sample(&mut self, control: ControlScheme) -> InputState {
+        self.gamepad.poll();
+        let mut _input = std::mem::take(&mut self.pending);
+        if self.touch.tick() == Some(Gesture::TwoFingerHold) {
+            _input.press(Action::Pause);
+        }
+        // a held mouse button fires like Shoot, a held finger only with the Hold scheme:
+        // otherwise taps toggle firing and the finger is only steering
+        if self.mouse_down || (control == ControlScheme::Hold && self.touch.is_touching()) {
+            _input.hold(Action::Shoot);
+        }
+        for _action in Action::ALL {
+            if self.keyboard.is_held(_action) || self.gamepad.is_held(_action) {
+                _input.hold(_action);
+            }
+            if self.keyboard.is_pressed(_action) || self.gamepad.is_pressed(_action) {
+                _input.press(_action);
+            }
+        }
+        let _x = self.keyboard.get_axis(Action::Left, Action::Right);
+        let _y = self.keyboard.get_axis(Action::Up, Action::Down);
+        (_input.axis_x, _input.axis_y) = if _x != 0.0 || _y != 0.0 {
+            (_x, _y)
+        } else {
+            self.gamepad.get_axis()
+        };
+        self.keyboard.end_tick();
+        _input
+    }
+}
@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use wasm_bindgen::JsCast;
+use wasm_bindgen::JsValue;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{Request, RequestInit, RequestMode, Response, window};
+
+use crate::Difficulty;
+pub use crate::highscore::{HighScore, HighScores, NAME_LENGTH};
+pub use crate::replay::Replay;
+use crate::world::Stage;
+
+// Leaderboard Protocol : JSON over HTTP
+//
+// POST {url}/runs                      Submission -> SubmitResult
+// GET  {url}/rankings?difficulty=Hard  -> Rankings
+//
+// The server doesn't trust the claimed score: it replays the attached replay
+// headlessly and accepts the run only if the replay clears the game with the
+// same score and clear time.
+
+pub const MAX_REPLAY_TICKS: u32 = 60 * 60 * 15; // 15 minutes of ticks
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Submission {
+    pub name: String,
+    pub score: i32,
+    pub milli_secondtime: i32,
+    pub replay: Replay,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SubmitResult {
+    pub accepted: bool,
+    pub rank: Option<usize>, // 0 = best, None if accepted but off the table
+    pub reason: Option<String>, // why the run was rejected
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Rankings {
+    pub difficulty: Difficulty,
+    pub entries: Vec<HighScore>,
+}
+
+/**
+ * difficulty from its name, for the rankings query
+ */
+pub fn parse_difficulty(name: &str) -> Option<Difficulty> {
+    Difficulty::ALL.into_iter().find(|d| d.name() == name)
+}
+
+/**
+ * replay a submission headless and check its claims
+ */
+pub fn verify(submission: &Submission) -> Result<(), String> {
+    let _name = submission.name.trim();
+    if _name.is_empty() || _name.chars().count() > NAME_LENGTH {
+        return Err("invalid name".to_string());
+    }
+    if submission.replay.get_ticks() > MAX_REPLAY_TICKS {
+        return Err("replay too long".to_string());
+    }
+    let _world = submission.replay.run();
+    if !matches!(_world.stage, Stage::Gameclear) {
+        return Err("the replay doesn't clear the game".to_string());
+    }
+    if _world.score != submission.score {
+        return Err(format!(
+            "score mismatch: claimed {}, replayed {}",
+            submission.score, _world.score
+        ));
+    }
+    if _world.get_milli_secondtime() != submission.milli_secondtime {
+        return Err(format!(
+            "time mismatch: claimed {}, replayed {}",
+            submission.milli_secondtime,
+            _world.get_milli_secondtime()
+        ));
+    }
+    Ok(())
+}
+
+// Client : fetch from the browser
+
+async fn fetch_text(url: &str, method: &str, body: Option<String>) -> Result<String, String> {
+    let _init = RequestInit::new();
+    _init.set_method(method);
+    _init.set_mode(RequestMode::Cors);
+    if let Some(_body) = body {
+        _init.set_body(&JsValue::from_str(&_body));
+    }
+    let _request = Request::new_with_str_and_init(url, &_init).map_err(|e| format!("{:?}", e))?;
+    if method == "POST" {
+        let _ = _request.headers().set("Content-Type", "application/json");
+    }
+    let _window = window().ok_or("no window")?;
+    let _response: Response = JsFuture::from(_window.fetch_with_request(&_request))
+        .await
+        .map_err(|_| "leaderboard unreachable".to_string())?
+        .dyn_into()
+        .map_err(|_| "bad response".to_string())?;
+    let _text = JsFuture::from(_response.text().map_err(|e| format!("{:?}", e))?)
+        .await
+        .map_err(|e| format!("{:?}", e))?
+        .as_string()
+        .unwrap_or_default();
+    if !_response.ok() {
+        return Err(format!("HTTP {}: {}", _response.status(), _text));
+    }
+    Ok(_text)
+}
+
+pub async fn submit(url: &str, submission: &Submission) -> Result<SubmitResult, String> {
+    let _body = serde_json::to_string(submission).map_err(|e| e.to_string())?;
+    let _text = fetch_text(&format!("{}/runs", url), "POST", Some(_body)).await?;
+    serde_json::from_str(&_text).map_err(|e| e.to_string())
+}
+
+pub async fn fetch_rankings(url: &str, difficulty: Difficulty) -> Result<Rankings, String> {
+    let _url = format!("{}/rankings?difficulty={}", url, difficulty.name());
+    let _text = fetch_text(&_url, "GET", None).await?;
+    serde_json::from_str(&_text).map_err(|e| e.to_string())
+}
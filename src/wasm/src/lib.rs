@@ -1,38 +1,142 @@
+mod achievement;
+mod audio;
+mod background;
+mod camera;
+mod highscore;
+mod hud;
+mod input;
+pub mod leaderboard;
+mod music;
+pub mod offline_audio;
+mod particle;
+mod replay;
+mod settings;
+mod sprite;
+mod storage;
+pub mod svg;
+mod world;
+
+use achievement::{ACHIEVEMENTS, Achievements, RunStats};
 use async_trait::async_trait;
-use rand::prelude::*;
+use audio::{Audio, Sfx};
+use background::Background;
+use camera::Camera;
+use highscore::{HighScore, HighScores, NAME_LENGTH};
+use hud::Hud;
+use input::{Action, InputDevices, InputState, KeyMap, PointerInput};
+use leaderboard::{Rankings, Submission, SubmitResult};
+use music::{MusicPlayer, Track};
+use serde::{Deserialize, Serialize};
+use settings::{Settings, SettingsMenu};
+use sprite::{Palette, SpriteId};
 use std::cmp::{max, min};
 use std::f64;
 use std::f64::consts::PI;
 use std::{cell::RefCell, rc::Rc};
+use storage::Storage;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::JsValue;
 use wasm_bindgen::prelude::*;
-use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, MouseEvent, TouchEvent, window};
+use web_sys::{
+    CanvasRenderingContext2d, Event, EventTarget, FocusEvent, GamepadEvent, HtmlCanvasElement,
+    KeyboardEvent, MouseEvent, PointerEvent, window,
+};
+use world::{GameEvent, MenuAction, PauseMenu, Stage, World};
 
 const CANVAS_WIDTH: f32 = 800.0;
 const CANVAS_HEIGHT: f32 = 1000.0;
 const BOSS_WIDTH: f32 = 225.0; // ENEMY BOSS WIDTH for hit judgement
 const BOSS_HEIGHT: f32 = 225.0; // ENEMY BOSS HEIGHT for hit judgement
 const BOSS_MAX_HP: i32 = 999; // ENEMY BOSS MAX Helath Point
+const BOSS_NAME: &str = "Verdant Fortress";
+const BOSS_EXPLOSION_TIME: i32 = 90; // ticks the defeated boss explodes before the clear
+const BOSS_EXPLOSION_INTERVAL: i32 = 6; // ticks between its bursts
 const MAX_BULLET_NUMBER: i32 = 500; // Number of BULLETS
 const BULLET_WIDTH: f32 = 28.0;
 const BULLET_HEIGHT: f32 = 28.0;
 const BULLET_STRENGTH: i32 = 2;
+const AIMED_INTERVAL: i32 = 30; // ticks between aimed bullets
+const AIMED_SPEED: f32 = 3.0;
+const PREDICTIVE_INTERVAL: i32 = 45; // ticks between lead-targeting bullets
+const PREDICTIVE_SPEED: f32 = 3.5;
+const SNIPE_INTERVAL: i32 = 90; // ticks between accelerating snipe bullets
+const SNIPE_START_SPEED: f32 = 0.5;
+const SNIPE_ACCELERATION: f32 = 0.1;
+const SNIPE_MAX_SPEED: f32 = 9.0; // on Normal, scaled like the bullet speed
+const SPLIT_TIME: i32 = 60; // ticks before a split bullet splits
+const SPLIT_NUMBER: i32 = 8; // pellets from a split bullet
+const SPLIT_SPEED: f32 = 2.5;
 const SHIP_WIDTH: f32 = 40.0;
 const SHIP_HEIGHT: f32 = 50.0;
 const SHIP_STEP: f32 = 1.0;
+const SHIP_KEY_STEP: f32 = 4.0; // keyboard movement per tick
+const SHIP_FOCUS_STEP: f32 = 1.5; // keyboard movement per tick while focusing
+const SHIP_MIN_Y: f32 = CANVAS_HEIGHT / 2.0; // the ship moves in the lower half
 const SHOT_WIDTH: f32 = 5.0;
 const SHOT_HEIGHT: f32 = 5.0;
 const SHOT_SPEED: f32 = 10.0;
 const SUPER_SHOT_WIDTH: f32 = 100.0;
 const SUPER_SHOT_HEIGHT: f32 = 40.0;
-const SUPER_WAIT_TIME: i32 = 200; // enery chage time
-const SUPER_TIME: i32 = 200; // super mode time
+const SUPER_SHOT_DAMAGE: i32 = 1; // damage per hit, a super shot hits up to SUPER_SHOT_HP times
+const SUPER_SHOT_HP: i32 = 3;
+const SUPER_GAUGE_MAX: i32 = 1000; // energy needed for super mode
+const SUPER_CHARGE_IDLE: i32 = 2; // energy per tick not shooting
+const SUPER_CHARGE_GRAZE: i32 = 25; // energy per grazed bullet
+const SUPER_CHARGE_DAMAGE: i32 = 1; // energy per boss damage
+const SUPER_TIME: i32 = 200; // super mode time (ticks)
+const SUPER_FLASH_TIME: i32 = 12; // screen flash on activation (ticks)
+const GRAZE_DISTANCE: f32 = 16.0; // bullets passing this close to the ship graze it
+const HOMING_WIDTH: f32 = 8.0;
+const HOMING_HEIGHT: f32 = 8.0;
+const HOMING_SPEED: f32 = 8.0;
+const HOMING_TURN_RATE: f32 = 0.06; // max steering angle per tick (radian)
+const HOMING_INTERVAL: i32 = 8; // ticks between homing missiles
+const HOMING_DAMAGE: i32 = 4;
+const LASER_WIDTH: f32 = 12.0;
+const LASER_DAMAGE: i32 = 1; // damage per tick
 const DEFAULT_COLOR: &str = "rgba(0,128, 0, 1.0)";
 const LIGHT_GREEN_COLOR: &str = "rgba(226,238,197,1.0)";
 const GREEN_DARK_LIGHT: &str = "rgba(17,31,17,1.0)";
 const LIGHT_YELLOR_GREEN: &str = "rgba(168,230,207,1.0)";
+const WHITE_COLOR: &str = "rgba(255,255,255,1.0)";
 const FPS: i32 = 16; // FPS 1000ms / 60frame
+const RANK_PER_DAMAGE: f32 = 0.0005; // rank gained per boss damage
+const RANK_PER_TICK: f32 = 0.0001; // rank gained per tick survived
+const RANK_DECAY: f32 = 0.0005; // rank lost per tick not shooting
+const RANK_PRESSURE: f32 = 0.5; // spawn rate & speed bonus at max rank
+const MENU_DIFFICULTY_Y: f32 = 620.0; // Openning menu: difficulty buttons
+const MENU_DIFFICULTY_X: f32 = 90.0;
+const MENU_DIFFICULTY_STEP: f32 = 160.0;
+const MENU_RANK_Y: f32 = 680.0; // Openning menu: rank toggle
+const MENU_SETTINGS_Y: f32 = 740.0; // Openning menu: settings
+const MENU_HIGHSCORE_Y: f32 = 800.0; // Openning menu: high scores
+const MENU_ACHIEVEMENT_Y: f32 = 860.0; // Openning menu: achievements
+const MENU_HEIGHT: f32 = 40.0;
+const SETTINGS_Y: f32 = 250.0; // Settings: first row
+const SETTINGS_STEP: f32 = 48.0;
+const SHAKE_BOMB: f64 = 10.0; // screen shake px on super mode activation
+const SHAKE_DEATH: f64 = 16.0; // on the ship's explosion
+const SHAKE_BOSS_DEFEATED: f64 = 24.0; // on the boss's explosion
+const KEYCONFIG_Y: f32 = 300.0; // Keyconfig: first action row
+const KEYCONFIG_STEP: f32 = 50.0;
+const KEYCONFIG_RESET_Y: f32 = 760.0;
+const KEYCONFIG_BACK_Y: f32 = 820.0;
+const ACHIEVEMENT_Y: f32 = 230.0; // Achievements: first row
+const ACHIEVEMENT_STEP: f32 = 70.0;
+const ACHIEVEMENT_BACK_Y: f32 = 840.0;
+const TOAST_TIME: i32 = 180; // ticks an unlock toast stays on screen
+const HIGHSCORE_TAB_Y: f32 = 240.0; // High scores: difficulty tabs
+const HIGHSCORE_Y: f32 = 300.0; // High scores: first entry row
+const HIGHSCORE_STEP: f32 = 45.0;
+const HIGHSCORE_BACK_Y: f32 = 820.0;
+const HIGHSCORE_ONLINE_Y: f32 = 880.0; // High scores: local / online toggle
+const SCORE_PER_DAMAGE: i32 = 10;
+const SCORE_PER_GRAZE: i32 = 50;
+const SCORE_PER_SECOND: i32 = 100; // clear bonus per second left of SCORE_TIME_LIMIT
+const SCORE_TIME_LIMIT: i32 = 300_000; // ms
+const PAUSE_MENU_Y: f32 = 460.0; // Pause menu: first item
+const PAUSE_MENU_STEP: f32 = 60.0;
+const MAX_CATCH_UP: i32 = 100; // max ms of updates run after a stalled frame
 
 macro_rules! log {
     ( $( $t:tt )* ) => {
@@ -40,6 +144,68 @@ macro_rules! log {
     }
 }
 
+// Difficulty
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+    Lunatic,
+}
+
+impl Difficulty {
+    const ALL: [Difficulty; 4] = [
+        Difficulty::Easy,
+        Difficulty::Normal,
+        Difficulty::Hard,
+        Difficulty::Lunatic,
+    ];
+    fn name(&self) -> &str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+            Difficulty::Lunatic => "Lunatic",
+        }
+    }
+    // bullet spawn rate multiplier
+    fn spawn_rate(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.5,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.5,
+            Difficulty::Lunatic => 2.0,
+        }
+    }
+    // bullet speed multiplier
+    fn speed(&self) -> f32 {
+        match self {
+            Difficulty::Easy => 0.75,
+            Difficulty::Normal => 1.0,
+            Difficulty::Hard => 1.2,
+            Difficulty::Lunatic => 1.4,
+        }
+    }
+    fn boss_hp(&self) -> i32 {
+        match self {
+            Difficulty::Easy => BOSS_MAX_HP / 2,
+            Difficulty::Normal => BOSS_MAX_HP,
+            Difficulty::Hard => BOSS_MAX_HP * 3 / 2,
+            Difficulty::Lunatic => BOSS_MAX_HP * 2,
+        }
+    }
+    fn max_bullets(&self) -> usize {
+        match self {
+            Difficulty::Easy => MAX_BULLET_NUMBER as usize / 2,
+            Difficulty::Normal => MAX_BULLET_NUMBER as usize,
+            Difficulty::Hard => MAX_BULLET_NUMBER as usize * 3 / 2,
+            Difficulty::Lunatic => MAX_BULLET_NUMBER as usize * 2,
+        }
+    }
+}
+
 // main : Wasm Access Point
 
 #[wasm_bindgen]
@@ -79,13 +245,18 @@ trait Character {
         let _obj_w = obj.get_w() as i32;
         let _obj_h = obj.get_h() as i32;
 
-        if max(_x, _obj_x) < min(_x + _w, _obj_x + _obj_w)
+        max(_x, _obj_x) < min(_x + _w, _obj_x + _obj_w)
             && (max(_y, _obj_y) < min(_y + _h, _obj_y + _obj_h))
-        {
-            return true;
-        } else {
-            false
-        }
+    }
+    // hit check with the hitbox grown by distance on every side
+    fn near(&self, obj: &dyn Character, distance: f32) -> bool {
+        let _x = self.get_x() - distance;
+        let _y = self.get_y() - distance;
+        let _w = self.get_w() + distance * 2.0;
+        let _h = self.get_h() + distance * 2.0;
+
+        _x.max(obj.get_x()) < (_x + _w).min(obj.get_x() + obj.get_w())
+            && _y.max(obj.get_y()) < (_y + _h).min(obj.get_y() + obj.get_h())
     }
     fn update(&mut self);
     fn draw(&self, ctx: CanvasRenderingContext2d);
@@ -93,10 +264,31 @@ trait Character {
 
 // Shot
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ShotType {
     Normal,
     Super,
+    Homing,
+    Laser,
+}
+
+impl ShotType {
+    fn name(&self) -> &str {
+        match self {
+            ShotType::Normal => "Normal",
+            ShotType::Super => "Super",
+            ShotType::Homing => "Homing",
+            ShotType::Laser => "Laser",
+        }
+    }
+    // weapon selected by right click
+    fn next_weapon(&self) -> ShotType {
+        match self {
+            ShotType::Normal => ShotType::Homing,
+            ShotType::Homing => ShotType::Laser,
+            _ => ShotType::Normal,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -111,6 +303,32 @@ struct Shot {
     t: ShotType,
 }
 
+impl Shot {
+    /**
+     * turn the center toward the target at most HOMING_TURN_RATE per tick
+     */
+    fn steer(&mut self, target: Option<(f32, f32)>) {
+        let Some((_tx, _ty)) = target else {
+            return;
+        };
+        let _x = self.x + self.w / 2.0;
+        let _y = self.y + self.h / 2.0;
+        // dy is upward positive
+        let _angle = libm::atan2f(-self.dy, self.dx);
+        let _target_angle = libm::atan2f(_ty - _y, _tx - _x);
+        let mut _diff = _target_angle - _angle;
+        while _diff > PI as f32 {
+            _diff -= 2.0 * PI as f32;
+        }
+        while _diff < -PI as f32 {
+            _diff += 2.0 * PI as f32;
+        }
+        let _new_angle = _angle + _diff.clamp(-HOMING_TURN_RATE, HOMING_TURN_RATE);
+        self.dx = libm::cosf(_new_angle) * HOMING_SPEED;
+        self.dy = -libm::sinf(_new_angle) * HOMING_SPEED;
+    }
+}
+
 impl Character for Shot {
     fn get_x(&self) -> f32 {
         self.x
@@ -125,6 +343,7 @@ impl Character for Shot {
         self.h
     }
     fn update(&mut self) {
+        self.x += self.dx;
         self.y -= self.dy;
     }
     fn draw(&self, ctx: CanvasRenderingContext2d) {
@@ -156,18 +375,257 @@ impl Character for Shot {
                 ctx.close_path();
                 ctx.fill();
             }
+            ShotType::Homing => {
+                // triangle pointing along the velocity
+                let _cx: f64 = (self.x + HOMING_WIDTH / 2.0).into();
+                let _cy: f64 = (self.y + HOMING_HEIGHT / 2.0).into();
+                let _angle: f64 = (-self.dy).atan2(self.dx).into();
+                let _r: f64 = HOMING_WIDTH.into();
+
+                ctx.begin_path();
+                ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
+                ctx.move_to(_cx + _angle.cos() * _r, _cy + _angle.sin() * _r);
+                ctx.line_to(
+                    _cx + (_angle + 2.5).cos() * _r,
+                    _cy + (_angle + 2.5).sin() * _r,
+                );
+                ctx.line_to(
+                    _cx + (_angle - 2.5).cos() * _r,
+                    _cy + (_angle - 2.5).sin() * _r,
+                );
+                ctx.close_path();
+                ctx.fill();
+            }
+            ShotType::Laser => {}
+        }
+    }
+}
+
+// Laser : not a moving shot, ray-casted from the ship every tick
+
+#[derive(Clone, Debug)]
+struct Laser {
+    x: f32,
+    y: f32,
+    w: f32,
+    h: f32,
+    target: Option<usize>, // index of the boss the ray stopped at
+}
+
+impl Laser {
+    /**
+     * cast a ray upward from (x, y), stopping at the first boss
+     */
+    fn cast(x: f32, y: f32, bosses: &[Boss]) -> Self {
+        let _left = x - LASER_WIDTH / 2.0;
+        let mut _top: f32 = 0.0;
+        let mut _target = None;
+        for (i, b) in bosses.iter().enumerate() {
+            if b.x < _left + LASER_WIDTH && _left < b.x + b.w && b.y < y && b.y + b.h > _top {
+                _top = (b.y + b.h).min(y);
+                _target = Some(i);
+            }
+        }
+        Laser {
+            x: _left,
+            y: _top,
+            w: LASER_WIDTH,
+            h: y - _top,
+            target: _target,
+        }
+    }
+}
+
+impl Character for Laser {
+    fn get_x(&self) -> f32 {
+        self.x
+    }
+    fn get_y(&self) -> f32 {
+        self.y
+    }
+    fn get_w(&self) -> f32 {
+        self.w
+    }
+    fn get_h(&self) -> f32 {
+        self.h
+    }
+    fn update(&mut self) {}
+    fn draw(&self, ctx: CanvasRenderingContext2d) {
+        ctx.set_fill_style_str(DEFAULT_COLOR);
+        ctx.fill_rect(self.x.into(), self.y.into(), self.w.into(), self.h.into());
+        ctx.set_fill_style_str(LIGHT_YELLOR_GREEN);
+        ctx.fill_rect(
+            (self.x + self.w / 4.0).into(),
+            self.y.into(),
+            (self.w / 2.0).into(),
+            self.h.into(),
+        );
+    }
+}
+// Bullet
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BulletType {
+    Round,  // default two-tone circle
+    Pellet, // small and fast
+    Rice,   // grain oriented along its velocity
+    Orb,    // large and durable
+    Knife,  // blade oriented along its velocity
+    Bounce, // reflects on the walls
+    Wrap,   // wraps around the left and right walls
+    Split,  // splits into pellets on a timer
+}
+
+/**
+ * hitbox, sprite, palette and lifetime rules of a bullet type
+ */
+struct BulletSpec {
+    w: f32, // hitbox width
+    h: f32, // hitbox height
+    hp: i32,
+    sprite: SpriteId,
+    palette: Palette,
+    life: i32,   // ticks before vanishing (or splitting), -1: until exit
+    bounce: i32, // number of wall bounces or wraps
+}
+
+impl BulletType {
+    const ALL: [BulletType; 8] = [
+        BulletType::Round,
+        BulletType::Pellet,
+        BulletType::Rice,
+        BulletType::Orb,
+        BulletType::Knife,
+        BulletType::Bounce,
+        BulletType::Wrap,
+        BulletType::Split,
+    ];
+    fn spec(&self) -> BulletSpec {
+        match self {
+            BulletType::Round => BulletSpec {
+                w: BULLET_WIDTH,
+                h: BULLET_HEIGHT,
+                hp: BULLET_STRENGTH,
+                sprite: SpriteId::BulletRound,
+                palette: Palette::GreenPale,
+                life: -1,
+                bounce: 0,
+            },
+            BulletType::Pellet => BulletSpec {
+                w: 8.0,
+                h: 8.0,
+                hp: 1,
+                sprite: SpriteId::BulletPellet,
+                palette: Palette::PaleGreen,
+                life: -1,
+                bounce: 0,
+            },
+            BulletType::Rice => BulletSpec {
+                w: 8.0,
+                h: 8.0,
+                hp: 1,
+                sprite: SpriteId::BulletRice,
+                palette: Palette::Mint,
+                life: -1,
+                bounce: 0,
+            },
+            BulletType::Orb => BulletSpec {
+                w: 48.0,
+                h: 48.0,
+                hp: 8,
+                sprite: SpriteId::BulletOrb,
+                palette: Palette::GreenPale,
+                life: -1,
+                bounce: 0,
+            },
+            BulletType::Knife => BulletSpec {
+                w: 10.0,
+                h: 10.0,
+                hp: 1,
+                sprite: SpriteId::BulletKnife,
+                palette: Palette::Pale,
+                life: -1,
+                bounce: 0,
+            },
+            BulletType::Bounce => BulletSpec {
+                w: 16.0,
+                h: 16.0,
+                hp: BULLET_STRENGTH,
+                sprite: SpriteId::BulletSmall,
+                palette: Palette::MintPale,
+                life: -1,
+                bounce: 3,
+            },
+            BulletType::Wrap => BulletSpec {
+                w: 16.0,
+                h: 16.0,
+                hp: BULLET_STRENGTH,
+                sprite: SpriteId::BulletSmall,
+                palette: Palette::PaleGreen,
+                life: -1,
+                bounce: 1,
+            },
+            BulletType::Split => BulletSpec {
+                w: 24.0,
+                h: 24.0,
+                hp: BULLET_STRENGTH,
+                sprite: SpriteId::BulletSplit,
+                palette: Palette::MintPale,
+                life: SPLIT_TIME,
+                bounce: 0,
+            },
         }
     }
 }
+
 #[derive(Clone, Debug)]
 struct Bullet {
     x: f32,
     y: f32,
     dx: f32,
     dy: f32,
+    ax: f32, // acceleration
+    ay: f32,
+    max_speed: f32,
     w: f32,
     h: f32,
     hp: i32,
+    t: BulletType,
+    life: i32,
+    bounce: i32,
+    grazed: bool,
+}
+
+impl Bullet {
+    /**
+     * create a bullet centered at (cx, cy)
+     */
+    fn new(t: BulletType, cx: f32, cy: f32, dx: f32, dy: f32) -> Self {
+        let _spec = t.spec();
+        Bullet {
+            x: cx - _spec.w / 2.0,
+            y: cy - _spec.h / 2.0,
+            dx,
+            dy,
+            ax: 0.0,
+            ay: 0.0,
+            max_speed: SNIPE_MAX_SPEED,
+            w: _spec.w,
+            h: _spec.h,
+            hp: _spec.hp,
+            t,
+            life: _spec.life,
+            bounce: _spec.bounce,
+            grazed: false,
+        }
+    }
+    fn get_center(&self) -> (f32, f32) {
+        (self.x + self.w / 2.0, self.y + self.h / 2.0)
+    }
+    // the timer ran out: vanish, or split for BulletType::Split
+    fn expired(&self) -> bool {
+        self.life == 0
+    }
 }
 
 impl Character for Bullet {
@@ -184,38 +642,164 @@ impl Character for Bullet {
         self.h
     }
     fn update(&mut self) {
+        if self.ax != 0.0 || self.ay != 0.0 {
+            self.dx += self.ax;
+            self.dy += self.ay;
+            let _speed = libm::hypotf(self.dx, self.dy);
+            if _speed > self.max_speed {
+                self.dx *= self.max_speed / _speed;
+                self.dy *= self.max_speed / _speed;
+                self.ax = 0.0;
+                self.ay = 0.0;
+            }
+        }
         self.x += self.dx;
         self.y += self.dy;
+        if self.life > 0 {
+            self.life -= 1;
+        }
+
+        // walls
+        if self.bounce > 0 {
+            match self.t {
+                BulletType::Bounce => {
+                    if (self.x < 0.0 && self.dx < 0.0)
+                        || (self.x + self.w > CANVAS_WIDTH && self.dx > 0.0)
+                    {
+                        self.x = self.x.clamp(0.0, CANVAS_WIDTH - self.w);
+                        self.dx = -self.dx;
+                        self.bounce -= 1;
+                    } else if self.y < 0.0 && self.dy < 0.0 {
+                        self.y = 0.0;
+                        self.dy = -self.dy;
+                        self.bounce -= 1;
+                    }
+                }
+                BulletType::Wrap => {
+                    if self.x < 0.0 {
+                        self.x += CANVAS_WIDTH;
+                        self.bounce -= 1;
+                    } else if self.x > CANVAS_WIDTH {
+                        self.x -= CANVAS_WIDTH;
+                        self.bounce -= 1;
+                    }
+                }
+                _ => {}
+            }
+        }
     }
     fn draw(&self, ctx: CanvasRenderingContext2d) {
-        let _x = &self.x;
-        let _y = &self.y;
+        let _spec = self.t.spec();
+        let (_cx, _cy) = self.get_center();
+        let (_w, _h) = (_spec.sprite.get().w as f32, _spec.sprite.get().h as f32);
 
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        let _ = ctx.arc(
-            (*_x).into(),
-            (*_y).into(),
-            (BULLET_WIDTH / 2.0).into(),
-            0.0,
-            (PI * 2.0).into(),
-        );
-        ctx.close_path();
-        ctx.fill();
-        ctx.begin_path();
-        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
-        let _ = ctx.arc(
-            (*_x).into(),
-            (*_y).into(),
-            (BULLET_WIDTH / 4.0).into(),
-            0.0,
-            (PI * 2.0).into(),
-        );
-        ctx.close_path();
-        ctx.fill();
+        match self.t {
+            BulletType::Rice | BulletType::Knife => {
+                // oriented along the velocity
+                ctx.save();
+                let _ = ctx.translate(_cx.into(), _cy.into());
+                let _ = ctx.rotate(self.dy.atan2(self.dx).into());
+                _spec.sprite.draw(&ctx, -_w / 2.0, -_h / 2.0, _spec.palette);
+                ctx.restore();
+            }
+            _ => {
+                _spec
+                    .sprite
+                    .draw(&ctx, _cx - _w / 2.0, _cy - _h / 2.0, _spec.palette);
+            }
+        }
     }
 }
 
+// Bullet Pattern
+
+#[derive(Clone, Copy, Debug)]
+enum Aim {
+    Random, // random direction, speed is the max of each axis
+    Ship,   // at the ship
+    Lead,   // at where the ship will be
+    Snipe,  // at the ship, accelerating up to a max speed
+}
+
+/**
+ * a bullet pattern fired by the boss every `interval` ticks
+ */
+struct BulletPattern {
+    interval: i32,
+    aim: Aim,
+    t: BulletType,
+    speed: f32,
+    ways: i32,   // number of bullets in a fan
+    spread: f32, // angle between the bullets of a fan (radian)
+}
+
+const BULLET_PATTERNS: [BulletPattern; 8] = [
+    BulletPattern {
+        interval: 1,
+        aim: Aim::Random,
+        t: BulletType::Round,
+        speed: 2.0,
+        ways: 1,
+        spread: 0.0,
+    },
+    BulletPattern {
+        interval: AIMED_INTERVAL,
+        aim: Aim::Ship,
+        t: BulletType::Rice,
+        speed: AIMED_SPEED,
+        ways: 3,
+        spread: 0.2,
+    },
+    BulletPattern {
+        interval: PREDICTIVE_INTERVAL,
+        aim: Aim::Lead,
+        t: BulletType::Knife,
+        speed: PREDICTIVE_SPEED,
+        ways: 1,
+        spread: 0.0,
+    },
+    BulletPattern {
+        interval: SNIPE_INTERVAL,
+        aim: Aim::Snipe,
+        t: BulletType::Pellet,
+        speed: SNIPE_START_SPEED,
+        ways: 1,
+        spread: 0.0,
+    },
+    BulletPattern {
+        interval: 120,
+        aim: Aim::Random,
+        t: BulletType::Orb,
+        speed: 1.0,
+        ways: 1,
+        spread: 0.0,
+    },
+    BulletPattern {
+        interval: 70,
+        aim: Aim::Random,
+        t: BulletType::Bounce,
+        speed: 2.5,
+        ways: 1,
+        spread: 0.0,
+    },
+    BulletPattern {
+        interval: 100,
+        aim: Aim::Random,
+        t: BulletType::Wrap,
+        speed: 2.0,
+        ways: 1,
+        spread: 0.0,
+    },
+    BulletPattern {
+        interval: 150,
+        aim: Aim::Ship,
+        t: BulletType::Split,
+        speed: 1.5,
+        ways: 1,
+        spread: 0.0,
+    },
+];
+
 #[derive(Clone, Debug)]
 struct Boss {
     x: f32,
@@ -225,7 +809,10 @@ struct Boss {
     w: f32,
     h: f32,
     hp: i32,
+    max_hp: i32,
     d: f32, // 0:left, 1:right
+    sprite: SpriteId,
+    palette: Palette,
 }
 
 impl Character for Boss {
@@ -250,13 +837,10 @@ impl Character for Boss {
             self.d = -1.0;
         }
         self.x += self.d * self.dx;
+        self.y += self.dy;
     }
     fn draw(&self, ctx: CanvasRenderingContext2d) {
-        let _txt = format!("{} / {}", &self.hp, BOSS_MAX_HP);
-        ctx.set_font("12px myfont");
-        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
-        let _ = ctx.fill_text(&_txt, (&self.x + 70.0).into(), (&self.y - 10.0).into());
-        draw_boss(&ctx, self.x, self.y);
+        self.sprite.draw(&ctx, self.x, self.y, self.palette);
     }
 }
 
@@ -271,18 +855,38 @@ struct Ship {
     w: f32,
     h: f32,
     hp: i32,
+    vx: f32, // velocity of the last update, used for lead-targeting
+    vy: f32,
     mouse_x: f32,
+    axis_x: f32, // keyboard direction, -1.0 - 1.0
+    axis_y: f32,
+    focus: bool,
+    drag_x: f32, // touch drag since the last update
+    drag_y: f32,
     t: ShotType,
+    sprite: SpriteId,
+    palette: Palette, // switched with the shot type
 }
 
 trait CharacterShip {
     fn set_mouse_x(&mut self, offset_x: f32);
+    fn set_axis(&mut self, axis_x: f32, axis_y: f32, focus: bool);
+    fn add_drag(&mut self, dx: f32, dy: f32);
 }
 
 impl CharacterShip for Ship {
     fn set_mouse_x(&mut self, offset_x: f32) {
         self.mouse_x = offset_x;
     }
+    fn set_axis(&mut self, axis_x: f32, axis_y: f32, focus: bool) {
+        self.axis_x = axis_x;
+        self.axis_y = axis_y;
+        self.focus = focus;
+    }
+    fn add_drag(&mut self, dx: f32, dy: f32) {
+        self.drag_x += dx;
+        self.drag_y += dy;
+    }
 }
 
 impl Character for Ship {
@@ -299,22 +903,36 @@ impl Character for Ship {
         self.h
     }
     fn update(&mut self) {
+        let (_x, _y) = (self.x, self.y);
         // direction
-        if self.mouse_x < self.x {
+        if self.axis_x != 0.0 || self.axis_y != 0.0 {
+            let _step = if self.focus {
+                SHIP_FOCUS_STEP
+            } else {
+                SHIP_KEY_STEP
+            };
+            self.x = (self.x + self.axis_x * _step).clamp(0.0, CANVAS_WIDTH - self.w);
+            self.y = (self.y + self.axis_y * _step).clamp(SHIP_MIN_Y, CANVAS_HEIGHT - self.h);
+            // the mouse takes over from here
+            self.mouse_x = self.x + self.w / 2.0;
+        } else if self.drag_x != 0.0 || self.drag_y != 0.0 {
+            // relative drag steering
+            self.x = (self.x + self.drag_x).clamp(0.0, CANVAS_WIDTH - self.w);
+            self.y = (self.y + self.drag_y).clamp(SHIP_MIN_Y, CANVAS_HEIGHT - self.h);
+            self.mouse_x = self.x + self.w / 2.0;
+            self.drag_x = 0.0;
+            self.drag_y = 0.0;
+        } else if self.mouse_x < self.x {
             self.x -= self.dx;
         } else if self.mouse_x > self.x + SHIP_WIDTH {
             self.x += self.dx;
         }
+        self.y += self.dy;
+        self.vx = self.x - _x;
+        self.vy = self.y - _y;
     }
     fn draw(&self, ctx: CanvasRenderingContext2d) {
-        match self.t {
-            ShotType::Normal => {
-                draw_ship(ctx, self.x, self.y);
-            }
-            ShotType::Super => {
-                draw_super_ship(ctx, self.x, self.y);
-            }
-        }
+        self.sprite.draw(&ctx, self.x, self.y, self.palette);
     }
 }
 
@@ -325,11 +943,17 @@ pub trait StaticGame {
     fn new(canvas: HtmlCanvasElement) -> Self;
     fn get_canvas(&mut self) -> HtmlCanvasElement;
     fn on_animation_frame(&mut self);
-    fn shot(&mut self, _x: i32, _y: i32);
+    fn key_down(&mut self, code: &str) -> bool;
+    fn key_up(&mut self, code: &str) -> bool;
+    fn blur(&mut self);
+    fn pointer_down(&mut self, p: PointerInput);
+    fn pointer_move(&mut self, p: PointerInput);
+    fn pointer_up(&mut self, p: PointerInput);
+    fn pointer_cancel(&mut self, p: PointerInput);
+    fn gamepad_connected(&mut self, index: u32);
+    fn gamepad_disconnected(&mut self, index: u32);
     fn update(&mut self);
     fn draw(&mut self);
-    fn reset(&mut self);
-    fn mouse_move(&mut self, mouse_x: f32);
 }
 
 // Game Loop
@@ -346,11 +970,13 @@ impl GameLoop {
         let closure_cloned = Rc::clone(&closure);
 
         let ref_game = Rc::new(RefCell::new(game));
-        let ref_game_clone = ref_game.clone();
         let ref_game_update_clone = ref_game.clone();
-        let ref_game_draw_clone = ref_game.clone();
-        let ref_game_mousemove_clone = ref_game.clone();
-        let ref_game_touchmove_clone = ref_game.clone();
+        let ref_game_pointer_clone = ref_game.clone();
+        let ref_game_keydown_clone = ref_game.clone();
+        let ref_game_keyup_clone = ref_game.clone();
+        let ref_game_blur_clone = ref_game.clone();
+        let ref_game_gamepadconnected_clone = ref_game.clone();
+        let ref_game_gamepaddisconnected_clone = ref_game.clone();
 
         let mut game_loop = GameLoop {
             last_frame: get_now(),
@@ -363,7 +989,8 @@ impl GameLoop {
         closure_cloned.replace(Some(Closure::wrap(Box::new(move |_time: i32| {
             // FPS 1/60
 
-            game_loop.accumulated_delta = _time - game_loop.last_frame;
+            // a hidden tab stops requestAnimationFrame, don't catch up the whole stall
+            game_loop.accumulated_delta += min(_time - game_loop.last_frame, MAX_CATCH_UP);
             while game_loop.accumulated_delta >= 0 {
                 // update start
                 ref_game_update_clone.borrow_mut().update();
@@ -379,42 +1006,140 @@ impl GameLoop {
 
         request_animation_frame(closure_cloned.borrow().as_ref().unwrap());
 
-        // mousedown event callback
+        // pointerdown / pointermove / pointerup / pointercancel event callback
 
-        let _mouse_down = Closure::wrap(Box::new(move |e: MouseEvent| {
-            ref_game_clone.borrow_mut().shot(e.client_x(), e.client_y());
+        for _type in ["pointerdown", "pointermove", "pointerup", "pointercancel"] {
+            let _canvas_clone = _canvas.clone();
+            let _ref_game_clone = ref_game_pointer_clone.clone();
+            let _pointer = Closure::wrap(Box::new(move |e: PointerEvent| {
+                // middle button autoscroll
+                if e.button() == 1 {
+                    e.prevent_default();
+                }
+                // keep the pointer's events coming when it leaves the canvas, a release outside
+                // would leave the mouse button held
+                if _type == "pointerdown" {
+                    let _ = _canvas_clone.set_pointer_capture(e.pointer_id());
+                }
+                let (_x, _y) = get_canvas_position(&_canvas_clone, e.client_x(), e.client_y());
+                let _input = PointerInput {
+                    id: e.pointer_id(),
+                    touch: e.pointer_type() == "touch",
+                    button: e.button(),
+                    x: _x,
+                    y: _y,
+                    time: e.time_stamp(),
+                };
+                let mut _game = _ref_game_clone.borrow_mut();
+                match _type {
+                    "pointerdown" => _game.pointer_down(_input),
+                    "pointermove" => _game.pointer_move(_input),
+                    "pointerup" => _game.pointer_up(_input),
+                    _ => _game.pointer_cancel(_input),
+                }
+            }) as Box<dyn FnMut(_)>);
+            _canvas
+                .add_event_listener_with_callback(_type, _pointer.as_ref().unchecked_ref())
+                .unwrap();
+            _pointer.forget();
+        }
+
+        // contextmenu event callback
+
+        let _context_menu = Closure::wrap(Box::new(move |e: MouseEvent| {
+            e.prevent_default();
         }) as Box<dyn FnMut(_)>);
         _canvas
-            .add_event_listener_with_callback("mousedown", _mouse_down.as_ref().unchecked_ref())
+            .add_event_listener_with_callback("contextmenu", _context_menu.as_ref().unchecked_ref())
             .unwrap();
-        _mouse_down.forget();
+        _context_menu.forget();
+
+        // keydown / keyup event callback : bound keys don't scroll the page
 
-        // mousemove event callback
+        let _key_down = Closure::wrap(Box::new(move |e: KeyboardEvent| {
+            if ref_game_keydown_clone.borrow_mut().key_down(&e.code()) {
+                e.prevent_default();
+            }
+        }) as Box<dyn FnMut(_)>);
+        _canvas
+            .add_event_listener_with_callback("keydown", _key_down.as_ref().unchecked_ref())
+            .unwrap();
+        _key_down.forget();
 
-        let _mouse_move = Closure::wrap(Box::new(move |e: MouseEvent| {
-            ref_game_mousemove_clone
-                .borrow_mut()
-                .mouse_move(e.offset_x() as f32);
+        let _key_up = Closure::wrap(Box::new(move |e: KeyboardEvent| {
+            if ref_game_keyup_clone.borrow_mut().key_up(&e.code()) {
+                e.prevent_default();
+            }
         }) as Box<dyn FnMut(_)>);
         _canvas
-            .add_event_listener_with_callback("mousemove", _mouse_move.as_ref().unchecked_ref())
+            .add_event_listener_with_callback("keyup", _key_up.as_ref().unchecked_ref())
             .unwrap();
-        _mouse_move.forget();
+        _key_up.forget();
+
+        // blur event callback : keyup never comes after losing focus
+
+        let _window = window().unwrap();
+        for _target in [_canvas.unchecked_ref::<EventTarget>(), &_window] {
+            let _ref_game_clone = ref_game_blur_clone.clone();
+            let _blur = Closure::wrap(Box::new(move |_e: FocusEvent| {
+                _ref_game_clone.borrow_mut().blur();
+            }) as Box<dyn FnMut(_)>);
+            _target
+                .add_event_listener_with_callback("blur", _blur.as_ref().unchecked_ref())
+                .unwrap();
+            _blur.forget();
+        }
 
-        // touchemove event callback
+        // visibilitychange event callback : the tab was hidden
 
-        let _touch_move = Closure::wrap(Box::new(move |e: TouchEvent| {
-            let all_touches = e.touches();
-            if all_touches.length() > 0 {
-                let touch = all_touches.get(0).unwrap();
-                let _x = touch.client_x();
-                ref_game_touchmove_clone.borrow_mut().mouse_move(_x as f32);
+        let _document = _window.document().unwrap();
+        let _document_clone = _document.clone();
+        let _visibility_change = Closure::wrap(Box::new(move |_e: Event| {
+            if _document_clone.hidden() {
+                ref_game_blur_clone.borrow_mut().blur();
             }
         }) as Box<dyn FnMut(_)>);
-        _canvas
-            .add_event_listener_with_callback("touchmove", _touch_move.as_ref().unchecked_ref())
+        _document
+            .add_event_listener_with_callback(
+                "visibilitychange",
+                _visibility_change.as_ref().unchecked_ref(),
+            )
             .unwrap();
-        _touch_move.forget();
+        _visibility_change.forget();
+
+        // gamepadconnected / gamepaddisconnected event callback : hot-plug
+
+        let _gamepad_connected = Closure::wrap(Box::new(move |e: GamepadEvent| {
+            if let Some(_gamepad) = e.gamepad() {
+                log!("gamepad connected: {}", _gamepad.id());
+                ref_game_gamepadconnected_clone
+                    .borrow_mut()
+                    .gamepad_connected(_gamepad.index());
+            }
+        }) as Box<dyn FnMut(_)>);
+        _window
+            .add_event_listener_with_callback(
+                "gamepadconnected",
+                _gamepad_connected.as_ref().unchecked_ref(),
+            )
+            .unwrap();
+        _gamepad_connected.forget();
+
+        let _gamepad_disconnected = Closure::wrap(Box::new(move |e: GamepadEvent| {
+            if let Some(_gamepad) = e.gamepad() {
+                log!("gamepad disconnected: {}", _gamepad.id());
+                ref_game_gamepaddisconnected_clone
+                    .borrow_mut()
+                    .gamepad_disconnected(_gamepad.index());
+            }
+        }) as Box<dyn FnMut(_)>);
+        _window
+            .add_event_listener_with_callback(
+                "gamepaddisconnected",
+                _gamepad_disconnected.as_ref().unchecked_ref(),
+            )
+            .unwrap();
+        _gamepad_disconnected.forget();
 
         Ok(())
     }
@@ -429,279 +1154,655 @@ fn request_animation_frame(f: &Closure<dyn FnMut(i32)>) {
 
 // Game Object
 
-#[derive(Debug, Clone)]
-enum Stage {
-    Openning,
-    Playing,
-    Gameover,
-    Gameclear,
+// leaderboard state, written when a fetch completes
+
+#[derive(Debug, Default)]
+struct Online {
+    status: Option<String>, // result of the last submission
+    rankings: Option<Rankings>,
 }
 
 #[derive(Debug, Clone)]
 struct Game {
     canvas: HtmlCanvasElement,
-    stage: Stage,
-    shooting: bool,
-    wait_time: i32,
-    super_time: i32,
-    ship: Ship,
-    bosses: Vec<Boss>,
-    bullets: Vec<Bullet>,
-    shots: Vec<Shot>,
+    world: World,
+    input: InputDevices,
+    key_config_action: Option<Action>, // waiting for a key on the Keyconfig screen
+    audio: Rc<dyn Audio>,
+    music: MusicPlayer,
+    camera: Camera,
+    background: Background,
+    hud: Hud,
+    last_stage: Stage, // stage of the last frame drawn, a change fades it out
+    skipped_input: InputState, // input of the ticks slow motion skipped, for the next stepped tick
+    storage: Rc<dyn Storage>,
+    high_scores: HighScores,
+    highscore_difficulty: Difficulty, // table shown on the High scores screen
+    highscore_rank: Option<usize>,    // rank of the last run in its table
+    name_entry: Option<String>,       // name typed after a qualifying run
+    leaderboard_url: Option<String>,  // from the canvas data-leaderboard attribute
+    online: Rc<RefCell<Online>>,
+    highscore_online: bool, // the High scores screen shows the leaderboard
+    settings: Settings,
+    settings_cursor: usize,
+    achievements: Achievements,
+    toasts: Vec<(&'static str, i32)>, // unlocked achievement names, ticks left on screen
+    fps: i32,                         // frames drawn in the last second
+    fps_frames: i32,
+    fps_milli_secondtime: i32,
+    // real-time clock for diagnostics only, the game clock is World::get_milli_secondtime
     max_passed_milli_secondtime: i32,
     max_passed_milli_secondtime_draw: i32,
     passed_milli_secondtime: i32,
     start_milli_secondtime: i32,
 }
 
-impl StaticGame for Game {
-    // init
+impl Game {
+    // playfield & HUD, also behind the pause menu
 
-    fn new(canvas: HtmlCanvasElement) -> Self {
-        Game {
-            canvas: canvas,
-            stage: Stage::Openning,
-            shooting: false,
-            wait_time: 0,
-            super_time: 0,
-            ship: Ship {
-                x: CANVAS_WIDTH / 2.0 - SHIP_WIDTH / 2.0,
-                y: CANVAS_HEIGHT - SHIP_HEIGHT - 10.0,
-                dx: SHIP_STEP,
-                dy: 0.0,
-                w: SHIP_WIDTH,
-                h: SHIP_HEIGHT,
-                hp: 1,
-                mouse_x: CANVAS_WIDTH / 2.0,
-                t: ShotType::Normal,
-            },
-            bosses: vec![Boss {
-                x: 180.0,
-                y: 60.0,
-                dx: 1.0,
-                dy: 0.0,
-                w: BOSS_WIDTH,
-                h: BOSS_HEIGHT,
-                hp: BOSS_MAX_HP,
-                d: 1.0,
-            }],
-            bullets: vec![],
-            shots: vec![],
-            start_milli_secondtime: get_now(),
-            passed_milli_secondtime: 0,
-            max_passed_milli_secondtime: 0,
-            max_passed_milli_secondtime_draw: 0,
+    fn draw_playing(&self, _context: &CanvasRenderingContext2d) {
+        // Screen Shake
+
+        self.camera.begin(_context);
+
+        // Draw background
+
+        self.background.draw(_context);
+
+        // Draw boss, white while it flashes
+
+        self.world.bosses.iter().for_each(|b| {
+            b.draw(_context.clone());
+            self.camera.draw_boss_flash(_context, b.sprite, b.x, b.y);
+        });
+
+        // Draw bullets
+
+        self.world
+            .bullets
+            .iter()
+            .for_each(|b| b.draw(_context.clone()));
+
+        // Draw shot
+
+        for i in 0..self.world.shots.len() {
+            self.world.shots[i].draw(_context.clone());
+        }
+
+        // Draw laser
+
+        if let Some(_laser) = &self.world.laser {
+            _laser.draw(_context.clone());
+        }
+        // Draw Ship
+
+        if self.world.ship.hp > 0 {
+            self.world.ship.draw(_context.clone());
+        }
+
+        // Draw particles
+
+        self.world
+            .particles
+            .iter()
+            .for_each(|p| p.draw(_context.clone()));
+        self.camera.end(_context);
+
+        // Draw HUD
+
+        let _bomb_key = self.input.keyboard.key_map.get_code(Action::Bomb);
+        self.hud.draw(_context, &self.world, _bomb_key);
+
+        // Draw Super Flash
+
+        if self.settings.flashes && self.world.super_flash > 0 {
+            _context
+                .set_global_alpha(self.world.super_flash as f64 / SUPER_FLASH_TIME as f64 * 0.6);
+            _context.set_fill_style_str(LIGHT_YELLOR_GREEN);
+            _context.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+            _context.set_global_alpha(1.0);
         }
     }
 
-    // get canvas
+    // the finished run as a high score entry
 
-    fn get_canvas(&mut self) -> HtmlCanvasElement {
-        self.canvas.clone()
+    fn get_run(&self, name: &str) -> HighScore {
+        HighScore {
+            score: self.world.score,
+            milli_secondtime: self.world.get_milli_secondtime(),
+            date: js_sys::Date::now(),
+            name: name.to_string(),
+            seed: self.world.seed,
+            difficulty: self.world.difficulty,
+        }
     }
 
-    // callback animation
+    // store the run under the typed name
 
-    fn on_animation_frame(&mut self) {
-        self.draw();
+    fn confirm_name(&mut self) {
+        let Some(_name) = self.name_entry.take() else {
+            return;
+        };
+        let _name = if _name.trim().is_empty() {
+            self.high_scores.get_last_name().to_string()
+        } else {
+            _name.trim().to_string()
+        };
+        let _run = self.get_run(&_name);
+        self.highscore_rank = self.high_scores.insert(_run);
+        self.high_scores.save(&*self.storage);
+        self.submit_run(&_name);
     }
 
-    fn mouse_move(&mut self, _x: f32) {
-        self.ship.set_mouse_x(_x);
+    // send the cleared run with its replay to the leaderboard
+
+    fn submit_run(&self, name: &str) {
+        let Some(_url) = self.leaderboard_url.clone() else {
+            return;
+        };
+        let _submission = Submission {
+            name: name.to_string(),
+            score: self.world.score,
+            milli_secondtime: self.world.get_milli_secondtime(),
+            replay: self.world.replay.clone(),
+        };
+        let _online = self.online.clone();
+        _online.borrow_mut().status = Some("Leaderboard: sending...".to_string());
+        wasm_bindgen_futures::spawn_local(async move {
+            let _status = match leaderboard::submit(&_url, &_submission).await {
+                Ok(SubmitResult {
+                    accepted: true,
+                    rank: Some(_rank),
+                    ..
+                }) => format!("Leaderboard rank: {}", _rank + 1),
+                Ok(SubmitResult { accepted: true, .. }) => "Leaderboard: accepted".to_string(),
+                Ok(SubmitResult { reason, .. }) => {
+                    format!("Leaderboard rejected: {}", reason.unwrap_or_default())
+                }
+                Err(e) => format!("Leaderboard: {}", e),
+            };
+            _online.borrow_mut().status = Some(_status);
+        });
     }
-    // callback click
-    fn shot(&mut self, _x: i32, _y: i32) {
-        match &self.stage {
-            Stage::Gameclear => {
-                self.stage = Stage::Playing;
-                self.reset();
+
+    // load the leaderboard table of the shown difficulty
+
+    fn fetch_rankings(&self) {
+        let Some(_url) = self.leaderboard_url.clone() else {
+            return;
+        };
+        let _difficulty = self.highscore_difficulty;
+        let _online = self.online.clone();
+        _online.borrow_mut().rankings = None;
+        wasm_bindgen_futures::spawn_local(async move {
+            match leaderboard::fetch_rankings(&_url, _difficulty).await {
+                Ok(_rankings) => _online.borrow_mut().rankings = Some(_rankings),
+                Err(e) => log!("leaderboard: {}", e),
             }
+        });
+    }
+
+    // a click on the title or pause menu, as the item under it
+
+    fn get_menu_action(&self, input: &InputState) -> Option<MenuAction> {
+        let (_x, _y) = input.click?;
+        match self.world.stage {
             Stage::Openning => {
-                self.stage = Stage::Playing;
+                if (MENU_DIFFICULTY_Y - MENU_HEIGHT..MENU_DIFFICULTY_Y).contains(&_y) {
+                    let i = ((_x - MENU_DIFFICULTY_X) / MENU_DIFFICULTY_STEP).floor();
+                    if (0.0..Difficulty::ALL.len() as f32).contains(&i) {
+                        return Some(MenuAction::Difficulty(Difficulty::ALL[i as usize]));
+                    }
+                }
+                let _action = if (MENU_RANK_Y - MENU_HEIGHT..MENU_RANK_Y).contains(&_y) {
+                    MenuAction::ToggleRank
+                } else if (MENU_SETTINGS_Y - MENU_HEIGHT..MENU_SETTINGS_Y).contains(&_y) {
+                    MenuAction::Settings
+                } else if (MENU_HIGHSCORE_Y - MENU_HEIGHT..MENU_HIGHSCORE_Y).contains(&_y) {
+                    MenuAction::Highscore
+                } else if (MENU_ACHIEVEMENT_Y - MENU_HEIGHT..MENU_ACHIEVEMENT_Y).contains(&_y) {
+                    MenuAction::Achievements
+                } else {
+                    MenuAction::Start
+                };
+                Some(_action)
             }
-            Stage::Gameover => {
-                self.reset();
+            // the keys act first, like the cursor
+            Stage::Paused
+                if !input.is_pressed(Action::Pause) && !input.is_pressed(Action::Shoot) =>
+            {
+                let i = ((_y - PAUSE_MENU_Y + PAUSE_MENU_STEP) / PAUSE_MENU_STEP).floor();
+                (0.0..PauseMenu::ALL.len() as f32)
+                    .contains(&i)
+                    .then(|| MenuAction::Pause(PauseMenu::ALL[i as usize]))
             }
-            Stage::Playing => {
-                self.wait_time = 0;
-                self.shooting = !self.shooting;
+            _ => None,
+        }
+    }
+
+    // high score screen: Left / Right or click a tab to switch the difficulty
+
+    fn update_highscore(&mut self, input: &InputState) {
+        if input.is_pressed(Action::Pause) {
+            self.world.stage = Stage::Openning;
+            return;
+        }
+        let _len = Difficulty::ALL.len();
+        let _index = Difficulty::ALL
+            .iter()
+            .position(|d| *d == self.highscore_difficulty)
+            .unwrap_or(0);
+        if input.is_pressed(Action::Left) {
+            self.highscore_difficulty = Difficulty::ALL[(_index + _len - 1) % _len];
+        }
+        if input.is_pressed(Action::Right) {
+            self.highscore_difficulty = Difficulty::ALL[(_index + 1) % _len];
+        }
+        let mut _fetch = self.highscore_difficulty != Difficulty::ALL[_index];
+        if let Some((_x, _y)) = input.click {
+            if (HIGHSCORE_TAB_Y - MENU_HEIGHT..HIGHSCORE_TAB_Y).contains(&_y) {
+                let i = ((_x - MENU_DIFFICULTY_X) / MENU_DIFFICULTY_STEP).floor();
+                if (0.0.._len as f32).contains(&i) {
+                    self.highscore_difficulty = Difficulty::ALL[i as usize];
+                    _fetch = true;
+                }
+            } else if (HIGHSCORE_BACK_Y - MENU_HEIGHT..HIGHSCORE_BACK_Y).contains(&_y) {
+                self.world.stage = Stage::Openning;
+            } else if (HIGHSCORE_ONLINE_Y - MENU_HEIGHT..HIGHSCORE_ONLINE_Y).contains(&_y)
+                && self.leaderboard_url.is_some()
+            {
+                self.highscore_online = !self.highscore_online;
+                _fetch = true;
             }
         }
+        if _fetch && self.highscore_online {
+            self.fetch_rankings();
+        }
     }
 
-    // restart
+    // achievements screen: Pause or Back returns to the title
 
-    fn reset(&mut self) {
-        self.stage = Stage::Openning;
-        self.shooting = false;
-        self.ship = Ship {
-            x: CANVAS_WIDTH / 2.0 - SHIP_WIDTH / 2.0,
-            y: CANVAS_HEIGHT - SHIP_HEIGHT - 10.0,
-            dx: SHIP_STEP,
-            dy: 0.0,
-            w: SHIP_WIDTH,
-            h: SHIP_HEIGHT,
-            hp: 1,
-            mouse_x: CANVAS_WIDTH / 2.0,
-            t: ShotType::Normal,
-        };
-        self.bosses = vec![Boss {
-            x: 180.0,
-            y: 60.0,
-            dx: 1.0,
-            dy: 0.0,
-            w: BOSS_WIDTH,
-            h: BOSS_HEIGHT,
-            hp: BOSS_MAX_HP,
-            d: 1.0,
-        }];
-        self.wait_time = 0;
-        self.super_time = 0;
-        self.bullets = vec![];
-        self.shots = vec![];
-        self.start_milli_secondtime = get_now();
-        self.max_passed_milli_secondtime = 0;
-        self.max_passed_milli_secondtime_draw = 0;
-        self.passed_milli_secondtime = 0;
-    }
-
-    // game controller
-    fn update(&mut self) {
-        match &self.stage {
-            Stage::Openning => {}
-            Stage::Gameclear => {}
-            Stage::Gameover => {}
-            Stage::Playing => {
-                //passed time
+    fn update_achievements(&mut self, input: &InputState) {
+        let _back = input.click.is_some_and(|(_, _y)| {
+            (ACHIEVEMENT_BACK_Y - MENU_HEIGHT..ACHIEVEMENT_BACK_Y).contains(&_y)
+        });
+        if _back || input.is_pressed(Action::Pause) {
+            self.world.stage = Stage::Openning;
+        }
+    }
 
-                let _start_process_milli_secondtime = get_now();
-                self.passed_milli_secondtime = get_now() - self.start_milli_secondtime;
+    // check a finished or abandoned run against the achievements, announce the new ones
 
-                // bullet create
-
-                if self.bullets.len() < MAX_BULLET_NUMBER as usize {
-                    let mut rnd = rand::thread_rng();
-                    let _x = self.bosses[0].x + BOSS_WIDTH / 2.0 - BULLET_WIDTH / 2.0;
-                    let _y = self.bosses[0].y + BOSS_WIDTH / 2.0 - BULLET_WIDTH / 2.0;
-                    let _dx: f32 = (rnd.gen_range(0..1000) as f32 / 1000.0 - 0.5) * 4.0;
-                    let _dy: f32 = (rnd.gen_range(0..1000) as f32 / 1000.0 - 0.5) * 4.0;
-                    let _bullet = Bullet {
-                        x: _x,
-                        y: _y,
-                        dx: _dx,
-                        dy: _dy,
-                        w: BULLET_WIDTH,
-                        h: BULLET_HEIGHT,
-                        hp: BULLET_STRENGTH,
-                    };
-                    self.bullets.push(_bullet);
-                }
-                // bullet update
-                self.bullets.iter_mut().for_each(|b| b.update());
-                self.bullets.retain(|b| b.exit() == false && *&b.hp > 0);
-
-                // shot create
-
-                if self.shooting {
-                    let _x = self.ship.x + SHIP_WIDTH / 2.0;
-                    let _y = self.ship.y;
-                    if self.super_time > 0 {
-                        let _shot = Shot {
-                            x: _x - SUPER_SHOT_WIDTH / 2.0,
-                            y: _y,
-                            dx: 0.0,
-                            dy: SHOT_SPEED,
-                            w: SUPER_SHOT_WIDTH,
-                            h: SUPER_SHOT_HEIGHT,
-                            hp: 3,
-                            t: ShotType::Super,
-                        };
-                        self.shots.push(_shot);
-                        self.super_time -= 1;
-                    } else {
-                        let _shot = Shot {
-                            x: _x,
-                            y: _y,
-                            dx: 0.0,
-                            dy: SHOT_SPEED,
-                            w: SHOT_WIDTH,
-                            h: SHOT_HEIGHT,
-                            hp: 1,
-                            t: ShotType::Normal,
-                        };
-                        self.shots.push(_shot);
-                    }
-                }
+    fn unlock_achievements(&mut self, run: &RunStats) {
+        let _unlocked = self.achievements.evaluate(run);
+        if _unlocked.is_empty() {
+            return;
+        }
+        self.achievements.save(&*self.storage);
+        self.toasts
+            .extend(_unlocked.iter().map(|a| (a.name, TOAST_TIME)));
+        self.audio.play(Sfx::Unlock);
+    }
 
-                // shots update
+    // push the settings into the world and the page
 
-                self.shots.retain(|s| s.exit() == false && *&s.hp > 0);
-                self.shots.iter_mut().for_each(|s| s.update());
+    fn apply_settings(&mut self) {
+        self.world.difficulty = self.settings.difficulty;
+        self.world.control = self.settings.control;
+        self.audio.set_mixer(self.settings.get_mixer());
+        let _ = self
+            .canvas
+            .style()
+            .set_property("filter", self.settings.palette.filter());
+    }
 
-                // hit check bullets x ship
+    // difficulty & fire mode are fixed until the paused run ends
 
-                self.bullets.iter_mut().for_each(|b| {
-                    if self.ship.hit(b) {
-                        self.stage = Stage::Gameover;
-                    }
-                });
+    fn is_setting_locked(&self, item: SettingsMenu) -> bool {
+        self.world.is_run_paused()
+            && matches!(item, SettingsMenu::Difficulty | SettingsMenu::Control)
+    }
 
-                // hit check shots x bullets
+    // settings screen: Up / Down to choose, Left / Right to change, Shoot or click to step
 
-                self.shots.iter_mut().for_each(|s| {
-                    self.bullets.iter_mut().for_each(|b| {
-                        if s.hit(b) {
-                            s.hp -= 1;
-                            b.hp -= 1;
-                        }
-                    })
-                });
+    fn update_settings(&mut self, input: &InputState) {
+        if input.is_pressed(Action::Pause) {
+            self.world.close_settings();
+            return;
+        }
+        let _len = SettingsMenu::ALL.len();
+        if input.is_pressed(Action::Up) {
+            self.settings_cursor = (self.settings_cursor + _len - 1) % _len;
+        }
+        if input.is_pressed(Action::Down) {
+            self.settings_cursor = (self.settings_cursor + 1) % _len;
+        }
+        let mut _step = 0;
+        if input.is_pressed(Action::Left) {
+            _step = -1;
+        }
+        if input.is_pressed(Action::Right) || input.is_pressed(Action::Shoot) {
+            _step = 1;
+        }
+        if let Some((_, _y)) = input.click {
+            let i = ((_y - SETTINGS_Y + MENU_HEIGHT) / SETTINGS_STEP).floor();
+            if !(0.0.._len as f32).contains(&i) {
+                return;
+            }
+            self.settings_cursor = i as usize;
+            _step = 1;
+        }
+        if _step == 0 {
+            return;
+        }
+        let _item = SettingsMenu::ALL[self.settings_cursor];
+        match _item {
+            SettingsMenu::Keyconfig => self.world.stage = Stage::Keyconfig,
+            SettingsMenu::Back => self.world.close_settings(),
+            _ if self.is_setting_locked(_item) => {}
+            _ => {
+                self.settings.change(_item, _step);
+                self.settings.save(&*self.storage);
+                self.apply_settings();
+            }
+        }
+    }
 
-                // hit check shots x boss
+    // key config screen: click an action then press a key
 
-                self.shots.iter_mut().for_each(|s| {
-                    self.bosses.iter_mut().for_each(|b| {
-                        if s.hit(b) {
-                            s.hp -= 1;
-                            b.hp -= 1;
-                        }
-                    })
-                });
+    fn update_keyconfig(&mut self, input: &InputState) {
+        if input.is_pressed(Action::Pause) {
+            self.key_config_action = None;
+            self.world.stage = Stage::Settings;
+            return;
+        }
+        let Some((_, _y)) = input.click else {
+            return;
+        };
+        let i = ((_y - KEYCONFIG_Y + MENU_HEIGHT) / KEYCONFIG_STEP).floor();
+        if (0.0..Action::ALL.len() as f32).contains(&i) {
+            self.key_config_action = Some(Action::ALL[i as usize]);
+        } else if (KEYCONFIG_RESET_Y - MENU_HEIGHT..KEYCONFIG_RESET_Y).contains(&_y) {
+            self.input.keyboard.key_map = KeyMap::default();
+            self.input.keyboard.key_map.save(&*self.storage);
+            self.key_config_action = None;
+        } else if (KEYCONFIG_BACK_Y - MENU_HEIGHT..KEYCONFIG_BACK_Y).contains(&_y) {
+            self.key_config_action = None;
+            self.world.stage = Stage::Settings;
+        }
+    }
+}
+
+impl StaticGame for Game {
+    // init
+
+    fn new(canvas: HtmlCanvasElement) -> Self {
+        let _storage = storage::open();
+        let _high_scores = HighScores::load(&*_storage);
+        let _settings = Settings::load(&*_storage);
+        let _achievements = Achievements::load(&*_storage);
+        let _leaderboard_url = canvas
+            .get_attribute("data-leaderboard")
+            .map(|u| u.trim_end_matches('/').to_string())
+            .filter(|u| !u.is_empty());
+        let mut _game = Game {
+            canvas,
+            world: World::new((js_sys::Math::random() * u64::MAX as f64) as u64),
+            input: InputDevices::new(KeyMap::load(&*_storage)),
+            key_config_action: None,
+            audio: audio::open(),
+            music: MusicPlayer::new(),
+            camera: Camera::default(),
+            background: Background::new(0),
+            hud: Hud::default(),
+            last_stage: Stage::Openning,
+            skipped_input: InputState::default(),
+            storage: _storage,
+            high_scores: _high_scores,
+            highscore_difficulty: Difficulty::Normal,
+            highscore_rank: None,
+            name_entry: None,
+            leaderboard_url: _leaderboard_url,
+            online: Rc::new(RefCell::new(Online::default())),
+            highscore_online: false,
+            settings: _settings,
+            settings_cursor: 0,
+            achievements: _achievements,
+            toasts: vec![],
+            fps: 0,
+            fps_frames: 0,
+            fps_milli_secondtime: get_now(),
+            start_milli_secondtime: get_now(),
+            passed_milli_secondtime: 0,
+            max_passed_milli_secondtime: 0,
+            max_passed_milli_secondtime_draw: 0,
+        };
+        _game.apply_settings();
+
+        // every sprite in every palette the game uses, so none is rendered while playing
+        sprite::prerender(
+            [
+                (SpriteId::Ship, Palette::Ship),
+                (SpriteId::Ship, Palette::SuperShip),
+                (SpriteId::Boss, Palette::Boss),
+                (SpriteId::Boss, Palette::Flash),
+            ]
+            .into_iter()
+            .chain(BulletType::ALL.iter().map(|t| {
+                let _spec = t.spec();
+                (_spec.sprite, _spec.palette)
+            })),
+        );
+        _game
+    }
 
-                // ship update
+    // get canvas
 
-                self.ship.update();
+    fn get_canvas(&mut self) -> HtmlCanvasElement {
+        self.canvas.clone()
+    }
 
-                // boss update
+    // callback animation
 
-                self.bosses[0].update();
+    fn on_animation_frame(&mut self) {
+        self.fps_frames += 1;
+        let _now = get_now();
+        if _now - self.fps_milli_secondtime >= 1000 {
+            self.fps = self.fps_frames;
+            self.fps_frames = 0;
+            self.fps_milli_secondtime = _now;
+        }
+        self.draw();
+    }
 
-                if self.bosses[0].hp <= 0 {
-                    self.bosses.retain(|s| *&s.hp > 0);
-                    // Game Clear
-                    if self.bosses.len() == 0 {
-                        self.stage = Stage::Gameclear;
+    // callback keydown
+    fn key_down(&mut self, code: &str) -> bool {
+        self.audio.unlock();
+        if let Some(_name) = &mut self.name_entry {
+            match code {
+                "Enter" | "NumpadEnter" => self.confirm_name(),
+                "Backspace" => {
+                    _name.pop();
+                }
+                _ => {
+                    let _char = code
+                        .strip_prefix("Key")
+                        .or_else(|| code.strip_prefix("Digit"))
+                        .filter(|c| c.len() == 1);
+                    if let Some(_char) = _char
+                        && _name.len() < NAME_LENGTH
+                    {
+                        _name.push_str(_char);
                     }
                 }
+            }
+            return true;
+        }
+        if let Some(_action) = self.key_config_action {
+            self.input.keyboard.key_map.bind(_action, code);
+            self.input.keyboard.key_map.save(&*self.storage);
+            self.key_config_action = None;
+            return true;
+        }
+        self.input.key_down(code)
+    }
+
+    // callback keyup
+    fn key_up(&mut self, code: &str) -> bool {
+        self.input.key_up(code)
+    }
 
-                // not shooting
+    // callback blur / visibilitychange : losing focus pauses the game
+    fn blur(&mut self) {
+        self.input.blur();
+        self.world.pause();
+    }
 
-                if self.shooting == false && self.super_time == 0 {
-                    self.wait_time += 1;
-                }
+    // callback pointerdown
+    fn pointer_down(&mut self, p: PointerInput) {
+        self.audio.unlock();
+        self.input.pointer_down(&p);
+    }
 
-                if self.wait_time > SUPER_WAIT_TIME {
-                    self.super_time = SUPER_TIME;
-                    self.ship.t = ShotType::Super;
-                }
+    // callback pointermove
+    fn pointer_move(&mut self, p: PointerInput) {
+        self.input.pointer_move(&p);
+    }
 
-                if self.super_time <= 1 {
-                    self.ship.t = ShotType::Normal;
-                }
+    // callback pointerup : a touch is a gesture once it is lifted
+    fn pointer_up(&mut self, p: PointerInput) {
+        self.audio.unlock();
+        self.input.pointer_up(&p);
+    }
+
+    // callback pointercancel
+    fn pointer_cancel(&mut self, p: PointerInput) {
+        self.input.pointer_cancel(&p);
+    }
+
+    // callback gamepadconnected
+    fn gamepad_connected(&mut self, index: u32) {
+        self.input.gamepad.connect(index);
+    }
+
+    // callback gamepaddisconnected
+    fn gamepad_disconnected(&mut self, index: u32) {
+        self.input.gamepad.disconnect(index);
+    }
 
-                if self.shooting == true {
-                    self.wait_time = 0;
+    // game controller : sample the devices, step the world, react to its events
+
+    fn update(&mut self) {
+        let mut _input = self.input.sample(self.world.control);
+        _input.merge(&self.skipped_input);
+        self.toasts.iter_mut().for_each(|(_, _ticks)| *_ticks -= 1);
+        self.toasts.retain(|(_, _ticks)| *_ticks > 0);
+        let _track = Track::for_stage(&self.world.stage, self.world.is_run_paused());
+        self.music.update(_track, &*self.audio);
+        self.camera.update();
+        // the world waits for the old stage to fade out
+        if self.camera.is_fading_out() {
+            return;
+        }
+        // slow motion while the boss explodes, input waits for the ticks the world steps
+        if self.settings.slow_motion
+            && self.world.boss_explosion.is_some()
+            && !self.camera.is_slow_tick()
+        {
+            self.skipped_input = _input;
+            return;
+        }
+        self.skipped_input = InputState::default();
+        if self.name_entry.is_some() {
+            // keys are typed by key_down, a click or the gamepad confirms
+            if _input.click.is_some() || _input.is_pressed(Action::Pause) {
+                self.confirm_name();
+            }
+            return;
+        }
+        match self.world.stage {
+            Stage::Settings => {
+                self.update_settings(&_input);
+                return;
+            }
+            Stage::Keyconfig => {
+                self.update_keyconfig(&_input);
+                return;
+            }
+            Stage::Highscore => {
+                self.update_highscore(&_input);
+                return;
+            }
+            Stage::Achievements => {
+                self.update_achievements(&_input);
+                return;
+            }
+            _ => {}
+        }
+        let _start_process_milli_secondtime = get_now();
+        match self.get_menu_action(&_input) {
+            Some(_action) => self.world.select_menu(_action),
+            None => self.world.step(&_input),
+        }
+        if let Stage::Highscore = self.world.stage {
+            self.highscore_difficulty = self.world.difficulty;
+            if self.highscore_online {
+                self.fetch_rankings();
+            }
+        }
+        // the title menu selects the difficulty too
+        if self.world.difficulty != self.settings.difficulty {
+            self.settings.difficulty = self.world.difficulty;
+            self.settings.save(&*self.storage);
+        }
+        for _event in self.world.take_events() {
+            if let Some(_sfx) = Sfx::for_event(&_event) {
+                self.audio.play(_sfx);
+            }
+            match _event {
+                GameEvent::SuperActivated if self.settings.screen_shake => {
+                    self.camera.shake(SHAKE_BOMB);
+                }
+                GameEvent::BossHit if self.settings.flashes => self.camera.flash_boss(),
+                GameEvent::BossDefeated if self.settings.screen_shake => {
+                    self.camera.shake(SHAKE_BOSS_DEFEATED);
+                }
+                GameEvent::Start => {
+                    self.background = Background::new(0);
+                    self.hud = Hud::default();
+                    self.start_milli_secondtime = get_now();
+                    self.passed_milli_secondtime = 0;
+                    self.max_passed_milli_secondtime = 0;
+                    self.max_passed_milli_secondtime_draw = 0;
+                    self.highscore_rank = None;
+                    self.online.borrow_mut().status = None;
+                }
+                GameEvent::Clear => {
+                    self.unlock_achievements(&self.world.get_run_stats());
+                    if self.high_scores.qualifies(&self.get_run("")) {
+                        self.name_entry = Some(self.high_scores.get_last_name().to_string());
+                    } else {
+                        self.submit_run(self.high_scores.get_last_name());
+                    }
+                }
+                GameEvent::Gameover => {
+                    if self.settings.screen_shake {
+                        self.camera.shake(SHAKE_DEATH);
+                    }
+                    self.unlock_achievements(&self.world.get_run_stats());
                 }
+                GameEvent::Abandoned(_run) => self.unlock_achievements(&_run),
+                _ => {}
+            }
+        }
+        match self.world.stage {
+            Stage::Playing => {
+                self.background.update(self.world.get_progress());
+                self.hud.update(&self.world);
+
+                // real passed time
+
+                self.passed_milli_secondtime = get_now() - self.start_milli_secondtime;
 
                 // mesure max passed time
                 self.max_passed_milli_secondtime = max(
@@ -709,6 +1810,11 @@ impl StaticGame for Game {
                     self.max_passed_milli_secondtime,
                 );
             }
+            // the clock stands still while paused
+            Stage::Paused => {
+                self.start_milli_secondtime = get_now() - self.passed_milli_secondtime;
+            }
+            _ => {}
         }
     }
 
@@ -723,48 +1829,268 @@ impl StaticGame for Game {
             .dyn_into::<CanvasRenderingContext2d>()
             .unwrap();
 
-        let _ = _context.set_global_alpha(1.0);
-        let _ = _context.clear_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+        // Fade : a new stage keeps the last frame of the old one on the canvas to fade out
+
+        if self.world.stage != self.last_stage {
+            let _pausing = matches!(
+                (&self.last_stage, &self.world.stage),
+                (Stage::Playing, Stage::Paused) | (Stage::Paused, Stage::Playing)
+            );
+            if self.settings.fade && !_pausing {
+                self.camera.fade_out(&self.canvas);
+            }
+            self.last_stage = self.world.stage.clone();
+        }
+
+        _context.set_global_alpha(1.0);
+        _context.clear_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+        if self.camera.is_fading_out() {
+            self.camera.draw_fade(&_context);
+            return;
+        }
 
-        match &self.stage {
+        match &self.world.stage {
             Stage::Openning => {
                 // Draw Title
-                draw_boss(&_context, 130.0, 20.0);
-                let _ = _context.set_font("60px myfont");
+                SpriteId::Boss.draw(&_context, 130.0, 20.0, Palette::Boss);
+                _context.set_font("60px myfont");
                 let _ = _context.fill_text("BARRAGE", 220.0, 360.0);
-                let _ = _context.set_fill_style_str(LIGHT_GREEN_COLOR);
-                let _ = _context.set_font("28px myfont");
+                _context.set_fill_style_str(LIGHT_GREEN_COLOR);
+                _context.set_font("28px myfont");
                 let _ = _context.fill_text("RUST & WASM", 270.0, 420.0);
-                let _ = _context.set_fill_style_str(DEFAULT_COLOR);
-                let _ = _context.set_font("28px myfont");
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("28px myfont");
                 let _ = _context.fill_text("Click Start", 300.0, 490.0);
-                draw_ship(
-                    _context.clone(),
+                _context.set_font("18px myfont");
+                let _ = _context.fill_text("Right Click: Change Weapon", 255.0, 540.0);
+
+                // Draw Difficulty Menu
+
+                _context.set_font("22px myfont");
+                for (i, _difficulty) in Difficulty::ALL.iter().enumerate() {
+                    _context.set_fill_style_str(if *_difficulty == self.world.difficulty {
+                        LIGHT_GREEN_COLOR
+                    } else {
+                        DEFAULT_COLOR
+                    });
+                    let _ = _context.fill_text(
+                        _difficulty.name(),
+                        (MENU_DIFFICULTY_X + MENU_DIFFICULTY_STEP * i as f32).into(),
+                        (MENU_DIFFICULTY_Y - 10.0).into(),
+                    );
+                }
+                _context.set_fill_style_str(if self.world.rank_enabled {
+                    LIGHT_GREEN_COLOR
+                } else {
+                    DEFAULT_COLOR
+                });
+                let _ = _context.fill_text(
+                    &format!(
+                        "Rank: {}",
+                        if self.world.rank_enabled { "ON" } else { "OFF" }
+                    ),
+                    320.0,
+                    (MENU_RANK_Y - 10.0).into(),
+                );
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                let _ = _context.fill_text("Settings", 320.0, (MENU_SETTINGS_Y - 10.0).into());
+                let _ = _context.fill_text("High Scores", 300.0, (MENU_HIGHSCORE_Y - 10.0).into());
+                let _ =
+                    _context.fill_text("Achievements", 290.0, (MENU_ACHIEVEMENT_Y - 10.0).into());
+                if self.input.gamepad.is_connected() {
+                    _context.set_font("18px myfont");
+                    let _ = _context.fill_text("Gamepad connected", 290.0, 900.0);
+                }
+                SpriteId::Ship.draw(
+                    &_context,
                     self.canvas.client_width() as f32 / 2.0 - SHIP_WIDTH / 2.0,
                     self.canvas.client_height() as f32 - SHIP_HEIGHT - 10.0,
+                    Palette::Ship,
                 );
             }
+            Stage::Settings => {
+                // Draw Title
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("60px myfont");
+                let _ = _context.fill_text("SETTINGS", 200.0, 150.0);
+
+                // Draw Settings
+
+                _context.set_font("24px myfont");
+                for (i, _item) in SettingsMenu::ALL.iter().enumerate() {
+                    _context.set_fill_style_str(if i == self.settings_cursor {
+                        LIGHT_GREEN_COLOR
+                    } else {
+                        DEFAULT_COLOR
+                    });
+                    let _y: f64 = (SETTINGS_Y + SETTINGS_STEP * i as f32 - 10.0).into();
+                    let _ = _context.fill_text(_item.name(), 150.0, _y);
+                    let _value = if self.is_setting_locked(*_item) {
+                        format!("{} (in run)", self.settings.get_value(*_item))
+                    } else {
+                        self.settings.get_value(*_item)
+                    };
+                    let _ = _context.fill_text(&_value, 420.0, _y);
+                }
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("18px myfont");
+                let _ = _context.fill_text("Click or Left / Right to change", 200.0, 900.0);
+            }
+            Stage::Keyconfig => {
+                // Draw Title
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("60px myfont");
+                let _ = _context.fill_text("CONTROLS", 200.0, 200.0);
+
+                // Draw Key Map
+
+                _context.set_font("24px myfont");
+                for (i, _action) in Action::ALL.iter().enumerate() {
+                    let _waiting = self.key_config_action == Some(*_action);
+                    _context.set_fill_style_str(if _waiting {
+                        LIGHT_GREEN_COLOR
+                    } else {
+                        DEFAULT_COLOR
+                    });
+                    let _y: f64 = (KEYCONFIG_Y + KEYCONFIG_STEP * i as f32 - 10.0).into();
+                    let _ = _context.fill_text(_action.name(), 200.0, _y);
+                    let _code = if _waiting {
+                        "Press a key"
+                    } else {
+                        self.input.keyboard.key_map.get_code(*_action)
+                    };
+                    let _ = _context.fill_text(_code, 400.0, _y);
+                }
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                let _ = _context.fill_text("Reset", 200.0, (KEYCONFIG_RESET_Y - 10.0).into());
+                let _ = _context.fill_text("Back", 200.0, (KEYCONFIG_BACK_Y - 10.0).into());
+                _context.set_font("18px myfont");
+                let _ = _context.fill_text("Click an action, then press a key", 200.0, 900.0);
+            }
+            Stage::Highscore => {
+                // Draw Title
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("60px myfont");
+                let _ = _context.fill_text("HIGH SCORES", 130.0, 150.0);
+
+                // Draw Difficulty Tabs
+
+                _context.set_font("22px myfont");
+                for (i, _difficulty) in Difficulty::ALL.iter().enumerate() {
+                    _context.set_fill_style_str(if *_difficulty == self.highscore_difficulty {
+                        LIGHT_GREEN_COLOR
+                    } else {
+                        DEFAULT_COLOR
+                    });
+                    let _ = _context.fill_text(
+                        _difficulty.name(),
+                        (MENU_DIFFICULTY_X + MENU_DIFFICULTY_STEP * i as f32).into(),
+                        (HIGHSCORE_TAB_Y - 10.0).into(),
+                    );
+                }
+
+                // Draw Entries
+
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                let _online = self.online.borrow();
+                let _table = if self.highscore_online {
+                    _online
+                        .rankings
+                        .as_ref()
+                        .filter(|r| r.difficulty == self.highscore_difficulty)
+                        .map_or(&[][..], |r| &r.entries)
+                } else {
+                    self.high_scores.get(self.highscore_difficulty)
+                };
+                if _table.is_empty() {
+                    let _ = _context.fill_text("No records yet", 290.0, HIGHSCORE_Y.into());
+                }
+                for (i, _entry) in _table.iter().enumerate() {
+                    let _y: f64 = (HIGHSCORE_Y + HIGHSCORE_STEP * i as f32).into();
+                    let _ = _context.fill_text(&format!("{}.", i + 1), 50.0, _y);
+                    let _ = _context.fill_text(&_entry.name, 110.0, _y);
+                    let _ = _context.fill_text(&_entry.score.to_string(), 320.0, _y);
+                    let _ = _context.fill_text(
+                        &format!("{} s.", get_passed_time(&_entry.milli_secondtime)),
+                        460.0,
+                        _y,
+                    );
+                    let _ = _context.fill_text(&get_date(_entry.date), 620.0, _y);
+                }
+                let _ = _context.fill_text("Back", 200.0, (HIGHSCORE_BACK_Y - 10.0).into());
+                if self.leaderboard_url.is_some() {
+                    let _ = _context.fill_text(
+                        if self.highscore_online {
+                            "Showing: Online"
+                        } else {
+                            "Showing: Local"
+                        },
+                        200.0,
+                        (HIGHSCORE_ONLINE_Y - 10.0).into(),
+                    );
+                }
+            }
+            Stage::Achievements => {
+                // Draw Title
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("60px myfont");
+                let _ = _context.fill_text("ACHIEVEMENTS", 110.0, 150.0);
+
+                // Draw Achievements
+
+                for (i, _achievement) in ACHIEVEMENTS.iter().enumerate() {
+                    let _unlocked = self.achievements.is_unlocked(_achievement);
+                    _context.set_fill_style_str(if _unlocked {
+                        LIGHT_GREEN_COLOR
+                    } else {
+                        DEFAULT_COLOR
+                    });
+                    let _y: f64 = (ACHIEVEMENT_Y + ACHIEVEMENT_STEP * i as f32 - 10.0).into();
+                    _context.set_font("24px myfont");
+                    let _ = _context.fill_text(if _unlocked { "[x]" } else { "[ ]" }, 100.0, _y);
+                    let _ = _context.fill_text(_achievement.name, 160.0, _y);
+                    _context.set_font("18px myfont");
+                    let _ = _context.fill_text(_achievement.description, 160.0, _y + 26.0);
+                }
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("24px myfont");
+                let _ = _context.fill_text("Back", 200.0, (ACHIEVEMENT_BACK_Y - 10.0).into());
+            }
             Stage::Gameover => {
+                // the ship explosion plays on behind the title
+                self.draw_playing(&_context);
+                _context.set_fill_style_str(GREEN_DARK_LIGHT);
+                _context.set_global_alpha(0.5);
+                _context.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+                _context.set_global_alpha(1.0);
+
                 // Draw Title
-                let _ = _context.set_font("60px myfont");
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("60px myfont");
                 let _ = _context.fill_text("GAME OVER", 150.0, 360.0);
-                let _ = _context.set_font("28px myfont");
-                let _ = _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("28px myfont");
+                _context.set_fill_style_str(DEFAULT_COLOR);
                 let _ = _context.fill_text("CLick Restart", 280.0, 420.0);
             }
             Stage::Gameclear => {
-                let _ = _context.set_fill_style_str(DEFAULT_COLOR);
-                let _ = _context.set_font("60px myfont");
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("60px myfont");
                 let _ = _context.fill_text("GAME CLEAR", 150.0, 360.0);
-                let _ = _context.set_fill_style_str(DEFAULT_COLOR);
-                let _ = _context.set_font("28px myfont");
+                _context.set_fill_style_str(DEFAULT_COLOR);
+                _context.set_font("28px myfont");
                 let _ = _context.fill_text("Congratiations!", 260.0, 420.0);
+                let _ = _context.fill_text(&format!("Score: {}", self.world.score), 200.0, 460.0);
                 let _cleartime = &format!(
                     "Your clear time: {} s.",
-                    get_passed_time(&(self.passed_milli_secondtime))
+                    get_passed_time(&self.world.get_milli_secondtime())
                 );
                 let _ = _context.fill_text(_cleartime, 200.0, 500.0);
-                let _ = _context.set_fill_style_str(LIGHT_GREEN_COLOR);
+                let _ = _context.fill_text(
+                    &format!("Difficulty: {}", self.world.difficulty.name()),
+                    200.0,
+                    550.0,
+                );
+                _context.set_fill_style_str(LIGHT_GREEN_COLOR);
                 let _max_update_time =
                     &format!("max update time: {} ms.", &self.max_passed_milli_secondtime);
                 let _ = _context.fill_text(_max_update_time, 200.0, 600.0);
@@ -773,51 +2099,67 @@ impl StaticGame for Game {
                     &self.max_passed_milli_secondtime_draw
                 );
                 let _ = _context.fill_text(_max_draw_time, 200.0, 650.0);
+                let _real_time = &format!(
+                    "real time: {} s.",
+                    get_passed_time(&self.passed_milli_secondtime)
+                );
+                let _ = _context.fill_text(_real_time, 200.0, 700.0);
+
+                // Draw Name Entry
+
+                _context.set_fill_style_str(LIGHT_GREEN_COLOR);
+                _context.set_font("28px myfont");
+                if let Some(_name) = &self.name_entry {
+                    let _ =
+                        _context.fill_text(&format!("NEW RECORD! Name: {}_", _name), 150.0, 770.0);
+                    _context.set_font("18px myfont");
+                    let _ = _context.fill_text(
+                        "Type your name, Enter / Click to confirm",
+                        150.0,
+                        810.0,
+                    );
+                } else if let Some(_rank) = self.highscore_rank {
+                    let _ = _context.fill_text(
+                        &format!("High score rank: {}", _rank + 1),
+                        200.0,
+                        770.0,
+                    );
+                }
+                if let Some(_status) = &self.online.borrow().status {
+                    _context.set_font("18px myfont");
+                    let _ = _context.fill_text(_status, 200.0, 860.0);
+                }
+            }
+            Stage::Paused => {
+                self.draw_playing(&_context);
+
+                // Draw Pause Menu
+
+                _context.set_fill_style_str(GREEN_DARK_LIGHT);
+                _context.set_global_alpha(0.7);
+                _context.fill_rect(0.0, 0.0, CANVAS_WIDTH as f64, CANVAS_HEIGHT as f64);
+                _context.set_global_alpha(1.0);
+                _context.set_fill_style_str(LIGHT_GREEN_COLOR);
+                _context.set_font("60px myfont");
+                let _ = _context.fill_text("PAUSE", 280.0, 360.0);
+                _context.set_font("28px myfont");
+                for (i, _item) in PauseMenu::ALL.iter().enumerate() {
+                    _context.set_fill_style_str(if i == self.world.pause_cursor {
+                        LIGHT_GREEN_COLOR
+                    } else {
+                        DEFAULT_COLOR
+                    });
+                    let _ = _context.fill_text(
+                        _item.name(),
+                        280.0,
+                        (PAUSE_MENU_Y + PAUSE_MENU_STEP * i as f32 - 10.0).into(),
+                    );
+                }
             }
             Stage::Playing => {
-                //passed time
-
                 let _start_process_milli_secondtime = get_now();
-                self.passed_milli_secondtime = get_now() - self.start_milli_secondtime;
-
-                //let _start_process_secondtime = get_now();
-
-                // Draw boss
-
-                self.bosses[0].draw(_context.clone());
-
-                // Draw bullets
-
-                self.bullets.iter().for_each(|b| b.draw(_context.clone()));
-
-                // Draw shot
 
-                for i in 0..self.shots.len() {
-                    self.shots[i].draw(_context.clone());
-                }
-                // Draw Ship
-
-                self.ship.draw(_context.clone());
-
-                // Draw Time
-
-                let _ = _context.set_font("28px myfont");
-                let _ = _context.set_fill_style_str(LIGHT_GREEN_COLOR);
-                let _str = get_passed_time(&(get_now() - self.start_milli_secondtime));
-                let _ = _context.fill_text(&_str, 30.0, 50.0).unwrap();
-
-                // Draw Number of Bullets
-
-                let _ = _context.set_font("18px myfont");
-                let _ = _context.set_fill_style_str(LIGHT_GREEN_COLOR);
-                let _bullet_number = self.bullets.len();
-                let _ = _context
-                    .fill_text(
-                        &format!("Bullets: {}", _bullet_number.to_string()),
-                        30.0,
-                        90.0,
-                    )
-                    .unwrap();
+                self.draw_playing(&_context);
 
                 // mesure max passed time
                 self.max_passed_milli_secondtime_draw = max(
@@ -826,9 +2168,54 @@ impl StaticGame for Game {
                 );
             }
         }
+
+        // Fade
+
+        self.camera.draw_fade(&_context);
+
+        // Draw FPS
+
+        if self.settings.show_fps {
+            _context.set_font("18px myfont");
+            _context.set_fill_style_str(LIGHT_GREEN_COLOR);
+            let _ = _context.fill_text(&format!("FPS: {}", self.fps), 660.0, 30.0);
+        }
+
+        // Draw Achievement Toasts
+
+        _context.set_font("22px myfont");
+        for (i, (_name, _)) in self.toasts.iter().enumerate() {
+            let _y = 60.0 + 40.0 * i as f64;
+            _context.set_fill_style_str(GREEN_DARK_LIGHT);
+            _context.fill_rect(160.0, _y - 28.0, 480.0, 36.0);
+            _context.set_fill_style_str(LIGHT_GREEN_COLOR);
+            let _ = _context.fill_text(&format!("Achievement: {}", _name), 180.0, _y);
+        }
     }
 }
 
+/**
+ * get canvas coordinates from client coordinates, the canvas may be scaled by CSS:
+ * the drawing fills the content box, inside the border
+ */
+fn get_canvas_position(canvas: &HtmlCanvasElement, client_x: i32, client_y: i32) -> (f32, f32) {
+    let _rect = canvas.get_bounding_client_rect();
+    let _scale_x = if canvas.client_width() > 0 {
+        canvas.width() as f64 / canvas.client_width() as f64
+    } else {
+        1.0
+    };
+    let _scale_y = if canvas.client_height() > 0 {
+        canvas.height() as f64 / canvas.client_height() as f64
+    } else {
+        1.0
+    };
+    (
+        ((client_x as f64 - _rect.left() - canvas.client_left() as f64) * _scale_x) as f32,
+        ((client_y as f64 - _rect.top() - canvas.client_top() as f64) * _scale_y) as f32,
+    )
+}
+
 /**
  * get string from now()
  */
@@ -838,6 +2225,19 @@ fn get_passed_time(passed_milli_secondtime: &i32) -> String {
     format!("{:<02}.{:<02}", _seconds, _mini_seconds)
 }
 
+/**
+ * date of a high score as YYYY-MM-DD
+ */
+fn get_date(date: f64) -> String {
+    let _date = js_sys::Date::new(&JsValue::from_f64(date));
+    format!(
+        "{}-{:02}-{:02}",
+        _date.get_full_year(),
+        _date.get_month() + 1,
+        _date.get_date()
+    )
+}
+
 fn get_now() -> i32 {
     let _window = window().expect("no global `window` exists");
     let _performance = _window
@@ -847,225 +2247,65 @@ fn get_now() -> i32 {
 }
 
 /**
- * draw character
+ * get the velocity to go straight from (x, y) to (target_x, target_y)
  */
-
-fn draw_super_ship(ctx: CanvasRenderingContext2d, x: f32, y: f32) {
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(LIGHT_YELLOR_GREEN);
-        ctx.move_to((17.0 + x).into(), y.into());
-        ctx.line_to((25.0 + x).into(), y.into());
-        ctx.line_to((25.0 + x).into(), (17.0 + y).into());
-        ctx.line_to((30.0 + x).into(), (17.0 + y).into());
-        ctx.line_to((30.0 + x).into(), (26.0 + y).into());
-        ctx.line_to((42.0 + x).into(), (47.0 + y).into());
-        ctx.line_to(x.into(), (47.0 + y).into());
-        ctx.line_to((12.0 + x).into(), (26.0 + y).into());
-        ctx.line_to((12.0 + x).into(), (17.0 + y).into());
-        ctx.line_to((17.0 + x).into(), (17.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        ctx.move_to((17.0 + x).into(), (27.0 + y).into());
-        ctx.line_to((24.0 + x).into(), (27.0 + y).into());
-        ctx.line_to((24.0 + x).into(), (45.0 + y).into());
-        ctx.line_to((17.0 + x).into(), (45.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
-        ctx.move_to((11.0 + x).into(), (47.0 + y).into());
-        ctx.line_to((17.0 + x).into(), (47.0 + y).into());
-        ctx.line_to((14.0 + x).into(), (56.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
-        ctx.move_to((26.0 + x).into(), (47.0 + y).into());
-        ctx.line_to((32.0 + x).into(), (47.0 + y).into());
-        ctx.line_to((29.0 + x).into(), (56.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
+fn get_aim(x: f32, y: f32, target_x: f32, target_y: f32, speed: f32) -> (f32, f32) {
+    let _angle = libm::atan2f(target_y - y, target_x - x);
+    (libm::cosf(_angle) * speed, libm::sinf(_angle) * speed)
 }
 
-fn draw_ship(ctx: CanvasRenderingContext2d, x: f32, y: f32) {
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        ctx.move_to((17.0 + x).into(), y.into());
-        ctx.line_to((25.0 + x).into(), y.into());
-        ctx.line_to((25.0 + x).into(), (17.0 + y).into());
-        ctx.line_to((30.0 + x).into(), (17.0 + y).into());
-        ctx.line_to((30.0 + x).into(), (26.0 + y).into());
-        ctx.line_to((42.0 + x).into(), (47.0 + y).into());
-        ctx.line_to(x.into(), (47.0 + y).into());
-        ctx.line_to((12.0 + x).into(), (26.0 + y).into());
-        ctx.line_to((12.0 + x).into(), (17.0 + y).into());
-        ctx.line_to((17.0 + x).into(), (17.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
-        ctx.move_to((17.0 + x).into(), (27.0 + y).into());
-        ctx.line_to((24.0 + x).into(), (27.0 + y).into());
-        ctx.line_to((24.0 + x).into(), (45.0 + y).into());
-        ctx.line_to((17.0 + x).into(), (45.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
-        ctx.move_to((11.0 + x).into(), (47.0 + y).into());
-        ctx.line_to((17.0 + x).into(), (47.0 + y).into());
-        ctx.line_to((14.0 + x).into(), (56.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
-        ctx.move_to((26.0 + x).into(), (47.0 + y).into());
-        ctx.line_to((32.0 + x).into(), (47.0 + y).into());
-        ctx.line_to((29.0 + x).into(), (56.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
+/**
+ * get the velocity to intercept a target moving at (vx, vy) per tick,
+ * falls back to get_aim when the bullet can not catch up
+ */
+fn get_lead_aim(
+    x: f32,
+    y: f32,
+    target_x: f32,
+    target_y: f32,
+    vx: f32,
+    vy: f32,
+    speed: f32,
+) -> (f32, f32) {
+    // solve |p + v t| = speed t for the earliest t > 0
+    let (_px, _py) = (target_x - x, target_y - y);
+    let _a = vx * vx + vy * vy - speed * speed;
+    let _b = 2.0 * (_px * vx + _py * vy);
+    let _c = _px * _px + _py * _py;
+    let _t = if _a.abs() < f32::EPSILON {
+        if _b.abs() < f32::EPSILON {
+            -1.0
+        } else {
+            -_c / _b
+        }
+    } else {
+        let _disc = _b * _b - 4.0 * _a * _c;
+        if _disc < 0.0 {
+            -1.0
+        } else {
+            let _t1 = (-_b - _disc.sqrt()) / (2.0 * _a);
+            let _t2 = (-_b + _disc.sqrt()) / (2.0 * _a);
+            match (_t1 > 0.0, _t2 > 0.0) {
+                (true, true) => _t1.min(_t2),
+                (true, false) => _t1,
+                (false, true) => _t2,
+                _ => -1.0,
+            }
+        }
+    };
+    if _t <= 0.0 {
+        return get_aim(x, y, target_x, target_y, speed);
     }
+    get_aim(x, y, target_x + vx * _t, target_y + vy * _t, speed)
 }
 
-fn draw_boss(ctx: &CanvasRenderingContext2d, x: f32, y: f32) {
-    {
-        ctx.set_fill_style_str(GREEN_DARK_LIGHT);
-        ctx.begin_path();
-        ctx.move_to((55.0 + x).into(), y.into());
-        ctx.line_to((165.0 + x).into(), y.into());
-        ctx.line_to((225.0 + x).into(), (60.0 + y).into());
-        ctx.line_to((225.0 + x).into(), (160.0 + y).into());
-        ctx.line_to((160.0 + x).into(), (225.0 + y).into());
-        ctx.line_to((55.0 + x).into(), (225.0 + y).into());
-        ctx.line_to(x.into(), (160.0 + y).into());
-        ctx.line_to(x.into(), (60.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(GREEN_DARK_LIGHT);
-        ctx.move_to((55.0 + x).into(), y.into());
-        ctx.line_to((165.0 + x).into(), y.into());
-        ctx.line_to((225.0 + x).into(), (60.0 + y).into());
-        ctx.line_to((225.0 + x).into(), (160.0 + y).into());
-        ctx.line_to((160.0 + x).into(), (225.0 + y).into());
-        ctx.line_to((55.0 + x).into(), (225.0 + y).into());
-        ctx.line_to(x.into(), (160.0 + y).into());
-        ctx.line_to(x.into(), (60.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_stroke_style_str(DEFAULT_COLOR);
-        ctx.move_to((55.0 + x).into(), (30.0 + y).into());
-        ctx.line_to((165.0 + x).into(), (30.0 + y).into());
-        ctx.line_to((195.0 + x).into(), (60.0 + y).into());
-        ctx.line_to((195.0 + x).into(), (160.0 + y).into());
-        ctx.line_to((160.0 + x).into(), (195.0 + y).into());
-        ctx.line_to((55.0 + x).into(), (195.0 + y).into());
-        ctx.line_to((26.0 + x).into(), (160.0 + y).into());
-        ctx.line_to((26.0 + x).into(), (60.0 + y).into());
-        ctx.close_path();
-        ctx.stroke();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        ctx.set_line_width(2.0);
-        ctx.move_to((85.0 + x).into(), (85.0 + y).into());
-        ctx.line_to((140.0 + x).into(), (85.0 + y).into());
-        ctx.line_to((140.0 + x).into(), (140.0 + y).into());
-        ctx.line_to((85.0 + x).into(), (140.0 + y).into());
-        ctx.close_path();
-        ctx.stroke();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        let _ = ctx.arc(
-            (112.0 + x).into(),
-            (112.0 + y).into(),
-            25.0,
-            0.0,
-            2.0 * PI as f64,
-        );
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(LIGHT_GREEN_COLOR);
-        let _ = ctx.arc(
-            (112.0 + x).into(),
-            (112.0 + y).into(),
-            20.0,
-            0.0,
-            2.0 * PI as f64,
-        );
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        ctx.set_line_width(2.0);
-        ctx.move_to((70.0 + x).into(), (40.0 + y).into());
-        ctx.line_to((152.0 + x).into(), (40.0 + y).into());
-        ctx.line_to((140.0 + x).into(), (77.0 + y).into());
-        ctx.line_to((80.0 + x).into(), (77.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        ctx.set_line_width(2.0);
-        ctx.move_to((84.0 + x).into(), (145.0 + y).into());
-        ctx.line_to((140.0 + x).into(), (145.0 + y).into());
-        ctx.line_to((152.0 + x).into(), (180.0 + y).into());
-        ctx.line_to((72.0 + x).into(), (180.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        ctx.set_line_width(2.0);
-        ctx.move_to((147.0 + x).into(), (85.0 + y).into());
-        ctx.line_to((183.0 + x).into(), (70.0 + y).into());
-        ctx.line_to((183.0 + x).into(), (155.0 + y).into());
-        ctx.line_to((147.0 + x).into(), (140.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
-    {
-        ctx.begin_path();
-        ctx.set_fill_style_str(DEFAULT_COLOR);
-        ctx.set_line_width(2.0);
-        ctx.move_to((80.0 + x).into(), (85.0 + y).into());
-        ctx.line_to((80.0 + x).into(), (140.0 + y).into());
-        ctx.line_to((40.0 + x).into(), (155.0 + y).into());
-        ctx.line_to((40.0 + x).into(), (70.0 + y).into());
-        ctx.close_path();
-        ctx.fill();
-    }
+/**
+ * get the nearest target point from (x, y)
+ */
+fn get_nearest(targets: &[(f32, f32)], x: f32, y: f32) -> Option<(f32, f32)> {
+    targets.iter().copied().min_by(|a, b| {
+        let _da = (a.0 - x).powi(2) + (a.1 - y).powi(2);
+        let _db = (b.0 - x).powi(2) + (b.1 - y).powi(2);
+        _da.total_cmp(&_db)
+    })
 }
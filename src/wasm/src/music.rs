@@ -0,0 +1,438 @@
+use crate::audio::{Audio, Waveform};
+use crate::world::Stage;
+
+const CROSSFADE_TICKS: i32 = 60; // ticks to fade one track into the next
+pub const DECKS: usize = 2; // the playing track and the one fading out
+
+// Channel : the voices of the sound chip, one note at a time each
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Channel {
+    Square,
+    Triangle,
+    Noise,
+}
+
+impl Channel {
+    pub const ALL: [Channel; 3] = [Channel::Square, Channel::Triangle, Channel::Noise];
+    pub fn waveform(&self) -> Waveform {
+        match self {
+            Channel::Square => Waveform::Square,
+            Channel::Triangle => Waveform::Triangle,
+            Channel::Noise => Waveform::Noise,
+        }
+    }
+    // mix level of the channel, 0.0 - 1.0
+    pub fn level(&self) -> f32 {
+        match self {
+            Channel::Square => 0.2,
+            Channel::Triangle => 0.5,
+            Channel::Noise => 0.3,
+        }
+    }
+}
+
+// Cell : one channel of one pattern row
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Cell {
+    Hold,     // "---" keep what is playing
+    Off,      // "===" silence the channel
+    Note(u8), // "C#4" MIDI note number
+}
+
+impl Cell {
+    fn parse(token: &str) -> Result<Cell, String> {
+        match token {
+            "---" => return Ok(Cell::Hold),
+            "===" => return Ok(Cell::Off),
+            _ => {}
+        }
+        let _chars: Vec<char> = token.chars().collect();
+        let [_name, _accidental, _octave] = _chars[..] else {
+            return Err(format!("bad note \"{}\"", token));
+        };
+        let _semitone = match _name {
+            'C' => 0,
+            'D' => 2,
+            'E' => 4,
+            'F' => 5,
+            'G' => 7,
+            'A' => 9,
+            'B' => 11,
+            _ => return Err(format!("bad note name \"{}\"", token)),
+        } + match _accidental {
+            '-' => 0,
+            '#' => 1,
+            _ => return Err(format!("bad accidental \"{}\"", token)),
+        };
+        let Some(_octave) = _octave.to_digit(10) else {
+            return Err(format!("bad octave \"{}\"", token));
+        };
+        Ok(Cell::Note(((_octave + 1) * 12 + _semitone) as u8))
+    }
+}
+
+/**
+ * frequency of a MIDI note, A4 (69) = 440 Hz
+ */
+pub fn get_frequency(note: u8) -> f32 {
+    440.0 * 2f32.powf((note as f32 - 69.0) / 12.0)
+}
+
+// Song : patterns of rows and the order they play in
+//
+// Text format, one statement per line, ";" starts a comment:
+//
+//   tempo 8              game ticks per row
+//   loop off             stop after the last pattern (default: on)
+//   pattern A            the rows below belong to pattern A
+//   C-5 C-3 ---          a row: square, triangle & noise cells
+//   sequence A A B       the order of the patterns
+//
+// A cell is a note (C-4, F#3), "---" to hold or "===" to stop the channel.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Song {
+    pub tempo: i32,
+    pub looping: bool,
+    patterns: Vec<(String, Vec<[Cell; 3]>)>,
+    sequence: Vec<usize>, // pattern indices
+}
+
+impl Song {
+    pub fn parse(text: &str) -> Result<Song, String> {
+        let mut _song = Song {
+            tempo: 8,
+            looping: true,
+            patterns: vec![],
+            sequence: vec![],
+        };
+        let mut _sequence: Vec<&str> = vec![];
+        for (i, _line) in text.lines().enumerate() {
+            let _error = |e: String| format!("line {}: {}", i + 1, e);
+            let _line = _line.split(';').next().unwrap_or("").trim();
+            let _tokens: Vec<&str> = _line.split_whitespace().collect();
+            match _tokens[..] {
+                [] => {}
+                ["tempo", _tempo] => {
+                    _song.tempo = _tempo
+                        .parse()
+                        .ok()
+                        .filter(|t| *t > 0)
+                        .ok_or(_error(format!("bad tempo \"{}\"", _tempo)))?;
+                }
+                ["loop", "on"] => _song.looping = true,
+                ["loop", "off"] => _song.looping = false,
+                ["pattern", _name] => _song.patterns.push((_name.to_string(), vec![])),
+                ["sequence", ..] => _sequence.extend(&_tokens[1..]),
+                [_square, _triangle, _noise] => {
+                    let Some((_, _rows)) = _song.patterns.last_mut() else {
+                        return Err(_error("row outside a pattern".to_string()));
+                    };
+                    _rows.push([
+                        Cell::parse(_square).map_err(_error)?,
+                        Cell::parse(_triangle).map_err(_error)?,
+                        Cell::parse(_noise).map_err(_error)?,
+                    ]);
+                }
+                _ => return Err(_error(format!("unknown statement \"{}\"", _line))),
+            }
+        }
+        for _name in _sequence {
+            let Some(_index) = _song.patterns.iter().position(|(n, _)| n == _name) else {
+                return Err(format!("unknown pattern \"{}\" in the sequence", _name));
+            };
+            _song.sequence.push(_index);
+        }
+        if _song
+            .sequence
+            .iter()
+            .all(|i| _song.patterns[*i].1.is_empty())
+        {
+            return Err("the sequence has no rows".to_string());
+        }
+        Ok(_song)
+    }
+}
+
+// Sequencer : steps through a song once per game tick
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sequencer {
+    position: usize, // index into the sequence
+    row: usize,
+    tick: i32, // ticks until the next row
+    finished: bool,
+}
+
+impl Sequencer {
+    pub fn new() -> Self {
+        Sequencer {
+            position: 0,
+            row: 0,
+            tick: 0,
+            finished: false,
+        }
+    }
+    /**
+     * advance one tick, returns the cells of a row when one starts
+     */
+    pub fn step(&mut self, song: &Song) -> Option<[Cell; 3]> {
+        if self.finished {
+            return None;
+        }
+        self.tick -= 1;
+        if self.tick > 0 {
+            return None;
+        }
+        self.tick = song.tempo;
+
+        // skip empty patterns & wrap around the sequence
+
+        while self.row >= song.patterns[song.sequence[self.position]].1.len() {
+            self.row = 0;
+            self.position += 1;
+            if self.position >= song.sequence.len() {
+                if !song.looping {
+                    self.finished = true;
+                    return Some([Cell::Off; 3]);
+                }
+                self.position = 0;
+            }
+        }
+        let _row = song.patterns[song.sequence[self.position]].1[self.row];
+        self.row += 1;
+        Some(_row)
+    }
+}
+
+// Track : the music of a part of the game
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Track {
+    Title,
+    Boss,
+    Clear,
+}
+
+impl Track {
+    const ALL: [Track; 3] = [Track::Title, Track::Boss, Track::Clear];
+    fn source(&self) -> &'static str {
+        match self {
+            Track::Title => include_str!("music/title.txt"),
+            Track::Boss => include_str!("music/boss.txt"),
+            Track::Clear => include_str!("music/clear.txt"),
+        }
+    }
+    /**
+     * music of a stage, the menus opened from the pause menu keep the run's music
+     */
+    pub fn for_stage(stage: &Stage, run_paused: bool) -> Option<Track> {
+        match stage {
+            Stage::Settings | Stage::Keyconfig if run_paused => Some(Track::Boss),
+            Stage::Openning
+            | Stage::Settings
+            | Stage::Keyconfig
+            | Stage::Highscore
+            | Stage::Achievements => Some(Track::Title),
+            Stage::Playing | Stage::Paused => Some(Track::Boss),
+            Stage::Gameclear => Some(Track::Clear),
+            Stage::Gameover => None,
+        }
+    }
+}
+
+// Deck : a track playing at some volume
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Deck {
+    track: Option<(Track, Sequencer)>,
+    volume: f32,
+}
+
+// Music Player : plays the track of the stage, crossfading between two decks
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct MusicPlayer {
+    songs: Vec<(Track, Song)>,
+    decks: [Deck; DECKS],
+    current: usize, // deck of the newest track
+}
+
+impl MusicPlayer {
+    pub fn new() -> Self {
+        // the songs are built in, a parse error is a bug
+        let _songs = Track::ALL.map(|t| {
+            let _song = Song::parse(t.source()).unwrap_or_else(|e| panic!("music {:?}: {}", t, e));
+            (t, _song)
+        });
+        MusicPlayer {
+            songs: _songs.to_vec(),
+            decks: Default::default(),
+            current: 0,
+        }
+    }
+
+    /**
+     * one game tick: switch to the track, fade the decks, play the rows that start
+     */
+    pub fn update(&mut self, track: Option<Track>, audio: &dyn Audio) {
+        let _playing = self.decks[self.current].track.as_ref().map(|(t, _)| *t);
+        if _playing != track {
+            self.current = (self.current + 1) % DECKS;
+            self.stop(self.current, audio);
+            self.decks[self.current].track = track.map(|t| (t, Sequencer::new()));
+            self.decks[self.current].volume = 0.0;
+        }
+        for (i, _deck) in self.decks.iter_mut().enumerate() {
+            let _fade = 1.0 / CROSSFADE_TICKS as f32;
+            _deck.volume = if i == self.current {
+                (_deck.volume + _fade).min(1.0)
+            } else {
+                (_deck.volume - _fade).max(0.0)
+            };
+            audio.set_deck_volume(i, _deck.volume);
+        }
+        for i in 0..DECKS {
+            if i != self.current && self.decks[i].volume <= 0.0 {
+                self.stop(i, audio);
+            }
+            let Some((_track, _sequencer)) = &mut self.decks[i].track else {
+                continue;
+            };
+            let Some((_, _song)) = self.songs.iter().find(|(t, _)| t == _track) else {
+                continue;
+            };
+            let Some(_row) = _sequencer.step(_song) else {
+                continue;
+            };
+            for (_channel, _cell) in Channel::ALL.into_iter().zip(_row) {
+                match _cell {
+                    Cell::Hold => {}
+                    Cell::Off => audio.note_off(i, _channel),
+                    Cell::Note(_note) => audio.note_on(i, _channel, get_frequency(_note)),
+                }
+            }
+        }
+    }
+
+    fn stop(&mut self, deck: usize, audio: &dyn Audio) {
+        if self.decks[deck].track.take().is_some() {
+            for _channel in Channel::ALL {
+                audio.note_off(deck, _channel);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SONG: &str = "
+        tempo 2
+        pattern A
+        C-4 --- ===
+        D#4 C-3 ---
+        pattern B
+        sequence A B A
+    ";
+
+    fn get_rows(song: &Song, ticks: usize) -> Vec<[Cell; 3]> {
+        let mut _sequencer = Sequencer::new();
+        (0..ticks).filter_map(|_| _sequencer.step(song)).collect()
+    }
+
+    #[test]
+    fn parse_reports_the_line_of_an_error() {
+        assert_eq!(
+            Song::parse("tempo 0").unwrap_err(),
+            "line 1: bad tempo \"0\""
+        );
+        assert_eq!(
+            Song::parse("tempo 8\nC-4 --- ---").unwrap_err(),
+            "line 2: row outside a pattern"
+        );
+        assert_eq!(
+            Song::parse("pattern A\nH-4 --- ---").unwrap_err(),
+            "line 2: bad note name \"H-4\""
+        );
+        assert_eq!(
+            Song::parse("pattern A\nC+4 --- ---").unwrap_err(),
+            "line 2: bad accidental \"C+4\""
+        );
+        assert_eq!(
+            Song::parse("pattern A\nC-x --- ---").unwrap_err(),
+            "line 2: bad octave \"C-x\""
+        );
+        assert_eq!(
+            Song::parse("pattern A\nC-4 --- -").unwrap_err(),
+            "line 2: bad note \"-\""
+        );
+        assert_eq!(
+            Song::parse("loop maybe").unwrap_err(),
+            "line 1: unknown statement \"loop maybe\""
+        );
+        assert_eq!(
+            Song::parse("pattern A\nC-4 --- ---\nsequence B").unwrap_err(),
+            "unknown pattern \"B\" in the sequence"
+        );
+        assert_eq!(
+            Song::parse("pattern A\nsequence A").unwrap_err(),
+            "the sequence has no rows"
+        );
+    }
+
+    #[test]
+    fn parse_reads_notes_and_options() {
+        let _song = Song::parse(SONG).unwrap();
+        assert_eq!(_song.tempo, 2);
+        assert!(_song.looping);
+        assert_eq!(
+            get_rows(&_song, 4),
+            [
+                [Cell::Note(60), Cell::Hold, Cell::Off],
+                [Cell::Note(63), Cell::Note(48), Cell::Hold],
+            ]
+        );
+    }
+
+    #[test]
+    fn sequencer_loops_over_empty_patterns() {
+        let _song = Song::parse(SONG).unwrap();
+        let _rows = get_rows(&_song, 20);
+        assert_eq!(_rows.len(), 10);
+        assert!(_rows.chunks(2).all(|c| c[0][0] == Cell::Note(60)));
+    }
+
+    #[test]
+    fn sequencer_stops_with_loop_off() {
+        let _song = Song::parse(&format!("loop off\n{}", SONG)).unwrap();
+        let _rows = get_rows(&_song, 20);
+        assert_eq!(_rows.len(), 5);
+        assert_eq!(_rows[4], [Cell::Off; 3]);
+    }
+
+    #[test]
+    fn built_in_tracks_parse() {
+        for _track in Track::ALL {
+            assert!(Song::parse(_track.source()).is_ok(), "{:?}", _track);
+        }
+    }
+
+    #[test]
+    fn menus_from_the_pause_menu_keep_the_run_music() {
+        assert_eq!(Track::for_stage(&Stage::Settings, true), Some(Track::Boss));
+        assert_eq!(Track::for_stage(&Stage::Keyconfig, true), Some(Track::Boss));
+        assert_eq!(
+            Track::for_stage(&Stage::Settings, false),
+            Some(Track::Title)
+        );
+        assert_eq!(
+            Track::for_stage(&Stage::Gameclear, true),
+            Some(Track::Clear)
+        );
+        assert_eq!(Track::for_stage(&Stage::Gameover, true), None);
+    }
+}
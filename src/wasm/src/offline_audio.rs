@@ -0,0 +1,324 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::io::Write;
+
+use crate::FPS;
+pub use crate::replay::Replay;
+
+use crate::audio::{
+    Audio, Mixer, NOISE_DECAY, NOISE_FREQ, NOTE_RELEASE, OUTPUT_GAIN, Sfx, Waveform,
+};
+use crate::music::{Channel, DECKS, MusicPlayer, Track};
+
+pub const SAMPLE_RATE: u32 = 44100;
+const TAIL_TICKS: i32 = 120; // ticks rendered after the run ends, for the last sounds to ring out
+const SILENCE: f32 = 0.001; // level exponential envelopes decay to, like the Web Audio backend
+
+// Offline Audio : the Web Audio graph mixed by hand into PCM samples
+//
+// Runs natively, so audio can be checked without a browser: render a replay,
+// write it to a WAV file or compare its envelope with a known good one.
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Bus {
+    Sfx,
+    Deck(usize),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Sound {
+    waveform: Waveform,
+    from_freq: f32,
+    to_freq: f32,
+    level: f32,
+    decay: Option<f64>, // seconds of sweep & decay, None = held until released
+    release: Option<(usize, f32)>, // sample the release started & the level then
+    start: usize,       // sample
+    bus: Bus,
+    note: Option<(usize, Channel)>, // the music note it plays
+    phase: f64,
+    noise: f32, // current noise value, held until the next noise step
+}
+
+#[derive(Debug)]
+struct State {
+    sample_rate: u32,
+    mixer: Mixer,
+    decks: [f32; DECKS],
+    sounds: Vec<Sound>,
+    last_played: HashMap<Sfx, usize>, // sample an effect last started
+    seed: u32,
+    samples: Vec<f32>,
+}
+
+impl State {
+    fn get_time(&self) -> f64 {
+        self.samples.len() as f64 / self.sample_rate as f64
+    }
+
+    fn add(&mut self, sound: Sound) {
+        self.sounds.push(sound);
+    }
+
+    fn release(&mut self, deck: usize, channel: Channel) {
+        let _now = self.samples.len();
+        let _sample_rate = self.sample_rate as f64;
+        for _sound in self.sounds.iter_mut() {
+            if _sound.note == Some((deck, channel)) && _sound.release.is_none() {
+                let _level = get_level(_sound, _now, _sample_rate).unwrap_or(0.0);
+                _sound.release = Some((_now, _level));
+                _sound.note = None;
+            }
+        }
+    }
+
+    fn next_noise(&mut self) -> f32 {
+        self.seed ^= self.seed << 13;
+        self.seed ^= self.seed >> 17;
+        self.seed ^= self.seed << 5;
+        self.seed as f32 / u32::MAX as f32 * 2.0 - 1.0
+    }
+
+    /**
+     * mix one more sample
+     */
+    fn mix(&mut self) {
+        let _now = self.samples.len();
+        let _sample_rate = self.sample_rate as f64;
+        let mut _sfx = 0.0;
+        let mut _decks = [0.0; DECKS];
+        let mut _sounds = std::mem::take(&mut self.sounds);
+        _sounds.retain_mut(|_sound| {
+            let Some(_level) = get_level(_sound, _now, _sample_rate) else {
+                return false;
+            };
+            let _t = (_now - _sound.start) as f64 / _sample_rate;
+            let _freq = match _sound.decay {
+                Some(_decay) => {
+                    let _progress = (_t / _decay).min(1.0) as f32;
+                    _sound.from_freq * (_sound.to_freq / _sound.from_freq).powf(_progress)
+                }
+                None => _sound.from_freq,
+            };
+            let _value = if _sound.waveform == Waveform::Noise {
+                // a noise buffer played back at freq / NOISE_FREQ
+                _sound.phase += (_freq / NOISE_FREQ) as f64;
+                if _sound.phase >= 1.0 {
+                    _sound.phase %= 1.0;
+                    _sound.noise = self.next_noise();
+                }
+                _sound.noise
+            } else {
+                _sound.phase = (_sound.phase + _freq as f64 / _sample_rate) % 1.0;
+                get_wave(_sound.waveform, _sound.phase as f32)
+            };
+            match _sound.bus {
+                Bus::Sfx => _sfx += _value * _level,
+                Bus::Deck(i) => _decks[i] += _value * _level,
+            }
+            true
+        });
+        self.sounds = _sounds;
+        let _music: f32 = _decks.iter().zip(self.decks).map(|(s, v)| s * v).sum();
+        let _mix =
+            (_sfx * self.mixer.sfx + _music * self.mixer.music) * self.mixer.master * OUTPUT_GAIN;
+        self.samples.push(_mix.clamp(-1.0, 1.0));
+    }
+}
+
+/**
+ * one cycle of an oscillator, phase 0.0 - 1.0
+ */
+fn get_wave(waveform: Waveform, phase: f32) -> f32 {
+    match waveform {
+        Waveform::Square => {
+            if phase < 0.5 {
+                1.0
+            } else {
+                -1.0
+            }
+        }
+        Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+        Waveform::Sawtooth => 2.0 * phase - 1.0,
+        Waveform::Noise => 0.0,
+    }
+}
+
+/**
+ * envelope level of a sound at a sample, None once it has ended
+ */
+fn get_level(sound: &Sound, now: usize, sample_rate: f64) -> Option<f32> {
+    if let Some((_start, _level)) = sound.release {
+        let _progress = (now - _start) as f64 / sample_rate / NOTE_RELEASE;
+        return (_progress < 1.0).then_some(_level * (1.0 - _progress as f32));
+    }
+    let _t = (now - sound.start) as f64 / sample_rate;
+    match sound.decay {
+        Some(_decay) if _t >= _decay => None,
+        Some(_decay) => Some(sound.level * (SILENCE / sound.level).powf((_t / _decay) as f32)),
+        None => Some(sound.level),
+    }
+}
+
+#[derive(Debug)]
+pub struct OfflineAudio {
+    state: RefCell<State>,
+}
+
+impl OfflineAudio {
+    pub fn new(sample_rate: u32) -> Self {
+        OfflineAudio {
+            state: RefCell::new(State {
+                sample_rate,
+                mixer: Mixer::default(),
+                decks: [0.0; DECKS],
+                sounds: vec![],
+                last_played: HashMap::new(),
+                seed: 0x1234_5678,
+                samples: vec![],
+            }),
+        }
+    }
+    /**
+     * mix the next milli seconds of sound
+     */
+    pub fn render(&self, milli_secondtime: i32) {
+        let mut _state = self.state.borrow_mut();
+        let _end = ((_state.get_time() * 1000.0 + milli_secondtime as f64)
+            * _state.sample_rate as f64
+            / 1000.0)
+            .round() as usize;
+        while _state.samples.len() < _end {
+            _state.mix();
+        }
+    }
+    pub fn take_samples(&self) -> Vec<f32> {
+        std::mem::take(&mut self.state.borrow_mut().samples)
+    }
+}
+
+impl Audio for OfflineAudio {
+    fn play(&self, sfx: Sfx) {
+        let mut _state = self.state.borrow_mut();
+        let _voice = sfx.voice();
+        let _now = _state.samples.len();
+        let _interval = (_voice.interval * _state.sample_rate as f64) as usize;
+        if let Some(_last) = _state.last_played.get(&sfx)
+            && _now - _last < _interval
+        {
+            return;
+        }
+        _state.last_played.insert(sfx, _now);
+        _state.add(Sound {
+            waveform: _voice.waveform,
+            from_freq: _voice.from_freq,
+            to_freq: _voice.to_freq,
+            level: _voice.volume,
+            decay: Some(_voice.duration),
+            release: None,
+            start: _now,
+            bus: Bus::Sfx,
+            note: None,
+            phase: 0.0,
+            noise: 0.0,
+        });
+    }
+    fn set_mixer(&self, mixer: Mixer) {
+        self.state.borrow_mut().mixer = mixer;
+    }
+    fn note_on(&self, deck: usize, channel: Channel, freq: f32) {
+        let mut _state = self.state.borrow_mut();
+        _state.release(deck, channel);
+        let _now = _state.samples.len();
+        _state.add(Sound {
+            waveform: channel.waveform(),
+            from_freq: freq,
+            to_freq: freq,
+            level: channel.level(),
+            decay: (channel == Channel::Noise).then_some(NOISE_DECAY),
+            release: None,
+            start: _now,
+            bus: Bus::Deck(deck),
+            note: Some((deck, channel)),
+            phase: 0.0,
+            noise: 0.0,
+        });
+    }
+    fn note_off(&self, deck: usize, channel: Channel) {
+        self.state.borrow_mut().release(deck, channel);
+    }
+    fn set_deck_volume(&self, deck: usize, volume: f32) {
+        if let Some(_deck) = self.state.borrow_mut().decks.get_mut(deck) {
+            *_deck = volume;
+        }
+    }
+    fn unlock(&self) {}
+}
+
+/**
+ * the sound of a replay: effects from its events and the music of its stages
+ */
+pub fn render_replay(replay: &Replay, sample_rate: u32) -> Vec<f32> {
+    let _audio = OfflineAudio::new(sample_rate);
+    let mut _music = MusicPlayer::new();
+    let _world = replay.run_with(|_world| {
+        for _event in _world.take_events() {
+            if let Some(_sfx) = Sfx::for_event(&_event) {
+                _audio.play(_sfx);
+            }
+        }
+        _music.update(Track::for_stage(&_world.stage, false), &_audio);
+        _audio.render(FPS);
+    });
+    let _track = Track::for_stage(&_world.stage, false);
+    for _ in 0..TAIL_TICKS {
+        _music.update(_track, &_audio);
+        _audio.render(FPS);
+    }
+    _audio.take_samples()
+}
+
+/**
+ * peak level of each window of samples, a coarse shape to compare renders with
+ */
+pub fn get_envelope(samples: &[f32], window: usize) -> Vec<f32> {
+    samples
+        .chunks(window.max(1))
+        .map(|c| c.iter().fold(0.0, |_peak: f32, s| _peak.max(s.abs())))
+        .collect()
+}
+
+/**
+ * 16-bit mono PCM WAV
+ */
+pub fn write_wav(
+    writer: &mut impl Write,
+    samples: &[f32],
+    sample_rate: u32,
+) -> std::io::Result<()> {
+    let _data_size = samples.len() as u32 * 2;
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(36 + _data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    // format: PCM, 1 channel, 16 bits
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    writer.write_all(&(sample_rate * 2).to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+
+    // samples
+
+    writer.write_all(b"data")?;
+    writer.write_all(&_data_size.to_le_bytes())?;
+    for _sample in samples {
+        let _value = (_sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+        writer.write_all(&_value.to_le_bytes())?;
+    }
+    Ok(())
+}
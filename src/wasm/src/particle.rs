@@ -0,0 +1,196 @@
+use rand::prelude::*;
+use std::f32::consts::PI;
+use web_sys::CanvasRenderingContext2d;
+
+use crate::{Character, DEFAULT_COLOR, LIGHT_GREEN_COLOR, LIGHT_YELLOR_GREEN};
+
+const MAX_PARTICLES: usize = 2000; // pool size, emitting into a full pool drops particles
+
+// Particle : a short-lived spark, drawn like any other character
+
+#[derive(Clone, Debug, Default)]
+pub struct Particle {
+    x: f32,
+    y: f32,
+    dx: f32,
+    dy: f32,
+    size: f32,
+    drag: f32, // velocity kept per tick, 0.0 - 1.0
+    life: i32, // ticks left
+    max_life: i32,
+    color: &'static str,
+}
+
+impl Character for Particle {
+    fn get_x(&self) -> f32 {
+        self.x
+    }
+    fn get_y(&self) -> f32 {
+        self.y
+    }
+    fn get_w(&self) -> f32 {
+        self.size
+    }
+    fn get_h(&self) -> f32 {
+        self.size
+    }
+    fn update(&mut self) {
+        self.x += self.dx;
+        self.y += self.dy;
+        self.dx *= self.drag;
+        self.dy *= self.drag;
+        self.life -= 1;
+    }
+    fn draw(&self, ctx: CanvasRenderingContext2d) {
+        ctx.set_global_alpha((self.life as f64 / self.max_life as f64).clamp(0.0, 1.0));
+        ctx.set_fill_style_str(self.color);
+        ctx.fill_rect(
+            (self.x - self.size / 2.0).into(),
+            (self.y - self.size / 2.0).into(),
+            self.size.into(),
+            self.size.into(),
+        );
+        ctx.set_global_alpha(1.0);
+    }
+}
+
+// Emitter : how a burst of particles looks, ranges are (min, max)
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Emitter {
+    pub count: usize,
+    pub direction: f32, // radian, 0.0 = right, PI / 2.0 = down
+    pub spread: f32,    // radian around the direction, 2 PI = every direction
+    pub speed: (f32, f32),
+    pub size: (f32, f32),
+    pub life: (i32, i32),
+    pub drag: f32,
+    pub colors: &'static [&'static str],
+}
+
+pub const BULLET_CANCEL: Emitter = Emitter {
+    count: 4,
+    direction: 0.0,
+    spread: 2.0 * PI,
+    speed: (0.5, 2.0),
+    size: (2.0, 4.0),
+    life: (10, 20),
+    drag: 0.92,
+    colors: &[DEFAULT_COLOR, LIGHT_YELLOR_GREEN],
+};
+
+pub const BOSS_HIT: Emitter = Emitter {
+    count: 2,
+    direction: PI / 2.0,
+    spread: PI / 2.0,
+    speed: (2.0, 5.0),
+    size: (2.0, 3.0),
+    life: (6, 12),
+    drag: 0.85,
+    colors: &[LIGHT_GREEN_COLOR, LIGHT_YELLOR_GREEN],
+};
+
+pub const SHIP_EXPLOSION: Emitter = Emitter {
+    count: 80,
+    direction: 0.0,
+    spread: 2.0 * PI,
+    speed: (1.0, 7.0),
+    size: (3.0, 7.0),
+    life: (30, 70),
+    drag: 0.95,
+    colors: &[LIGHT_GREEN_COLOR, LIGHT_YELLOR_GREEN, DEFAULT_COLOR],
+};
+
+pub const BOSS_LAST_EXPLOSION: Emitter = Emitter {
+    count: 200,
+    direction: 0.0,
+    spread: 2.0 * PI,
+    speed: (2.0, 12.0),
+    size: (4.0, 12.0),
+    life: (40, 90),
+    drag: 0.95,
+    colors: &[LIGHT_GREEN_COLOR, LIGHT_YELLOR_GREEN, DEFAULT_COLOR],
+};
+
+pub const BOSS_EXPLOSION: Emitter = Emitter {
+    count: 30,
+    direction: 0.0,
+    spread: 2.0 * PI,
+    speed: (1.0, 6.0),
+    size: (4.0, 10.0),
+    life: (20, 50),
+    drag: 0.93,
+    colors: &[LIGHT_GREEN_COLOR, LIGHT_YELLOR_GREEN, DEFAULT_COLOR],
+};
+
+// Particles : a fixed pool, the live particles are kept at the front
+
+#[derive(Clone, Debug)]
+pub struct Particles {
+    pool: Vec<Particle>,
+    alive: usize,
+    rng: StdRng, // apart from the world's generator, effects never change the game
+}
+
+impl Particles {
+    pub fn new(seed: u64) -> Self {
+        Particles {
+            pool: Vec::with_capacity(MAX_PARTICLES),
+            alive: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    pub fn emit(&mut self, emitter: &Emitter, x: f32, y: f32) {
+        for _ in 0..emitter.count {
+            if self.alive >= MAX_PARTICLES {
+                return;
+            }
+            let _angle = emitter.direction + emitter.spread * (self.rng.r#gen::<f32>() - 0.5);
+            let _speed = self.rng.gen_range(emitter.speed.0..=emitter.speed.1);
+            let _life = self.rng.gen_range(emitter.life.0..=emitter.life.1);
+            let _particle = Particle {
+                x,
+                y,
+                dx: _angle.cos() * _speed,
+                dy: _angle.sin() * _speed,
+                size: self.rng.gen_range(emitter.size.0..=emitter.size.1),
+                drag: emitter.drag,
+                life: _life,
+                max_life: _life,
+                color: emitter.colors[self.rng.gen_range(0..emitter.colors.len())],
+            };
+            if self.alive < self.pool.len() {
+                self.pool[self.alive] = _particle;
+            } else {
+                self.pool.push(_particle);
+            }
+            self.alive += 1;
+        }
+    }
+
+    // a burst at a random point of a rectangle
+    pub fn emit_in(&mut self, emitter: &Emitter, x: f32, y: f32, w: f32, h: f32) {
+        let _x = x + w * self.rng.r#gen::<f32>();
+        let _y = y + h * self.rng.r#gen::<f32>();
+        self.emit(emitter, _x, _y);
+    }
+
+    pub fn update(&mut self) {
+        let mut i = 0;
+        while i < self.alive {
+            self.pool[i].update();
+            if self.pool[i].life <= 0 {
+                // move the last live particle into the slot
+                self.alive -= 1;
+                self.pool.swap(i, self.alive);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Particle> {
+        self.pool[..self.alive].iter()
+    }
+}
@@ -0,0 +1,72 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Difficulty;
+use crate::input::InputState;
+use crate::settings::ControlScheme;
+use crate::world::{Stage, World};
+
+// Replay : everything needed to play a run again
+//
+// The world is deterministic: the same seed, options and inputs give the same game.
+// Only the ticks the world advanced are recorded, pauses are left out.
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub difficulty: Difficulty,
+    pub rank_enabled: bool,
+    pub control: ControlScheme,
+    pub inputs: Vec<(u32, InputState)>, // run-length encoded (ticks, input)
+}
+
+impl Replay {
+    pub fn new(
+        seed: u64,
+        difficulty: Difficulty,
+        rank_enabled: bool,
+        control: ControlScheme,
+    ) -> Self {
+        Replay {
+            seed,
+            difficulty,
+            rank_enabled,
+            control,
+            inputs: vec![],
+        }
+    }
+    pub fn record(&mut self, input: &InputState) {
+        match self.inputs.last_mut() {
+            Some((_ticks, _last)) if _last == input => *_ticks += 1,
+            _ => self.inputs.push((1, input.clone())),
+        }
+    }
+    pub fn get_ticks(&self) -> u32 {
+        self.inputs.iter().map(|(_ticks, _)| _ticks).sum()
+    }
+    /**
+     * play the run headless until it ends or the inputs run out
+     */
+    pub(crate) fn run(&self) -> World {
+        self.run_with(|_| {})
+    }
+    /**
+     * play the run headless, calling on_tick after every step
+     */
+    pub(crate) fn run_with(&self, mut on_tick: impl FnMut(&mut World)) -> World {
+        let mut _world = World::new(self.seed);
+        _world.difficulty = self.difficulty;
+        _world.rank_enabled = self.rank_enabled;
+        _world.control = self.control;
+        _world.start_with_seed(self.seed);
+        for (_ticks, _input) in &self.inputs {
+            for _ in 0..*_ticks {
+                if !matches!(_world.stage, Stage::Playing) {
+                    return _world;
+                }
+                _world.step(_input);
+                on_tick(&mut _world);
+            }
+        }
+        _world
+    }
+}
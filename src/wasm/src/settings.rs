@@ -0,0 +1,227 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Difficulty;
+use crate::audio::Mixer;
+use crate::storage::Storage;
+
+const SETTINGS_STORAGE_KEY: &str = "barrage.settings";
+pub const MAX_VOLUME: i32 = 10;
+
+// Control Scheme : how the ship fires
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ControlScheme {
+    Toggle, // click toggles firing, Shoot fires while held
+    Hold,   // fires only while the mouse button, a finger or Shoot is held
+    Auto,   // always fires
+}
+
+impl ControlScheme {
+    const ALL: [ControlScheme; 3] = [
+        ControlScheme::Toggle,
+        ControlScheme::Hold,
+        ControlScheme::Auto,
+    ];
+    pub fn name(&self) -> &str {
+        match self {
+            ControlScheme::Toggle => "Toggle",
+            ControlScheme::Hold => "Hold",
+            ControlScheme::Auto => "Auto",
+        }
+    }
+}
+
+// Palette : a CSS filter over the green canvas
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Palette {
+    Green,
+    Amber,
+    Blue,
+    Mono,
+}
+
+impl Palette {
+    const ALL: [Palette; 4] = [Palette::Green, Palette::Amber, Palette::Blue, Palette::Mono];
+    pub fn name(&self) -> &str {
+        match self {
+            Palette::Green => "Green",
+            Palette::Amber => "Amber",
+            Palette::Blue => "Blue",
+            Palette::Mono => "Mono",
+        }
+    }
+    pub fn filter(&self) -> &str {
+        match self {
+            Palette::Green => "none",
+            Palette::Amber => "hue-rotate(-80deg) saturate(1.5)",
+            Palette::Blue => "hue-rotate(90deg)",
+            Palette::Mono => "grayscale(1)",
+        }
+    }
+}
+
+// Settings Menu : rows of the settings screen
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SettingsMenu {
+    Volume,
+    SfxVolume,
+    MusicVolume,
+    Control,
+    Difficulty,
+    ScreenShake,
+    Flashes,
+    SlowMotion,
+    Fade,
+    ShowFps,
+    Palette,
+    Keyconfig,
+    Back,
+}
+
+impl SettingsMenu {
+    pub const ALL: [SettingsMenu; 13] = [
+        SettingsMenu::Volume,
+        SettingsMenu::SfxVolume,
+        SettingsMenu::MusicVolume,
+        SettingsMenu::Control,
+        SettingsMenu::Difficulty,
+        SettingsMenu::ScreenShake,
+        SettingsMenu::Flashes,
+        SettingsMenu::SlowMotion,
+        SettingsMenu::Fade,
+        SettingsMenu::ShowFps,
+        SettingsMenu::Palette,
+        SettingsMenu::Keyconfig,
+        SettingsMenu::Back,
+    ];
+    pub fn name(&self) -> &str {
+        match self {
+            SettingsMenu::Volume => "Master volume",
+            SettingsMenu::SfxVolume => "SFX volume",
+            SettingsMenu::MusicVolume => "Music volume",
+            SettingsMenu::Control => "Fire",
+            SettingsMenu::Difficulty => "Difficulty",
+            SettingsMenu::ScreenShake => "Screen shake",
+            SettingsMenu::Flashes => "Flashes",
+            SettingsMenu::SlowMotion => "Slow motion",
+            SettingsMenu::Fade => "Screen fades",
+            SettingsMenu::ShowFps => "Show FPS",
+            SettingsMenu::Palette => "Palette",
+            SettingsMenu::Keyconfig => "Controls",
+            SettingsMenu::Back => "Back",
+        }
+    }
+}
+
+// Settings : saved as JSON, applied on startup
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub volume: i32, // master, 0 - MAX_VOLUME
+    pub sfx_volume: i32,
+    pub music_volume: i32,
+    pub control: ControlScheme,
+    pub difficulty: Difficulty,
+    pub screen_shake: bool,
+    pub flashes: bool,     // boss hit & super mode flashes
+    pub slow_motion: bool, // on the boss's defeat
+    pub fade: bool,        // between stages
+    pub show_fps: bool,
+    pub palette: Palette,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Settings {
+            volume: MAX_VOLUME / 2,
+            sfx_volume: MAX_VOLUME,
+            music_volume: MAX_VOLUME,
+            control: ControlScheme::Toggle,
+            difficulty: Difficulty::Normal,
+            screen_shake: true,
+            flashes: true,
+            slow_motion: true,
+            fade: true,
+            show_fps: false,
+            palette: Palette::Green,
+        }
+    }
+}
+
+/**
+ * next or previous value of a list
+ */
+fn cycle<T: Copy + PartialEq>(all: &[T], value: T, step: i32) -> T {
+    let _len = all.len() as i32;
+    let _index = all.iter().position(|v| *v == value).unwrap_or(0) as i32;
+    all[(_index + step).rem_euclid(_len) as usize]
+}
+
+impl Settings {
+    pub fn load(storage: &dyn Storage) -> Self {
+        storage
+            .load(SETTINGS_STORAGE_KEY)
+            .and_then(|_json| serde_json::from_str(&_json).ok())
+            .unwrap_or_default()
+    }
+    pub fn save(&self, storage: &dyn Storage) {
+        if let Ok(_json) = serde_json::to_string(self) {
+            storage.save(SETTINGS_STORAGE_KEY, &_json);
+        }
+    }
+    // step through the values of a row, +1 or -1
+    pub fn change(&mut self, item: SettingsMenu, step: i32) {
+        match item {
+            SettingsMenu::Volume => {
+                self.volume = (self.volume + step).rem_euclid(MAX_VOLUME + 1);
+            }
+            SettingsMenu::SfxVolume => {
+                self.sfx_volume = (self.sfx_volume + step).rem_euclid(MAX_VOLUME + 1);
+            }
+            SettingsMenu::MusicVolume => {
+                self.music_volume = (self.music_volume + step).rem_euclid(MAX_VOLUME + 1);
+            }
+            SettingsMenu::Control => {
+                self.control = cycle(&ControlScheme::ALL, self.control, step);
+            }
+            SettingsMenu::Difficulty => {
+                self.difficulty = cycle(&Difficulty::ALL, self.difficulty, step);
+            }
+            SettingsMenu::ScreenShake => self.screen_shake = !self.screen_shake,
+            SettingsMenu::Flashes => self.flashes = !self.flashes,
+            SettingsMenu::SlowMotion => self.slow_motion = !self.slow_motion,
+            SettingsMenu::Fade => self.fade = !self.fade,
+            SettingsMenu::ShowFps => self.show_fps = !self.show_fps,
+            SettingsMenu::Palette => self.palette = cycle(&Palette::ALL, self.palette, step),
+            SettingsMenu::Keyconfig | SettingsMenu::Back => {}
+        }
+    }
+    pub fn get_value(&self, item: SettingsMenu) -> String {
+        let _on_off = |b: bool| if b { "ON" } else { "OFF" }.to_string();
+        match item {
+            SettingsMenu::Volume => format!("{} / {}", self.volume, MAX_VOLUME),
+            SettingsMenu::SfxVolume => format!("{} / {}", self.sfx_volume, MAX_VOLUME),
+            SettingsMenu::MusicVolume => format!("{} / {}", self.music_volume, MAX_VOLUME),
+            SettingsMenu::Control => self.control.name().to_string(),
+            SettingsMenu::Difficulty => self.difficulty.name().to_string(),
+            SettingsMenu::ScreenShake => _on_off(self.screen_shake),
+            SettingsMenu::Flashes => _on_off(self.flashes),
+            SettingsMenu::SlowMotion => _on_off(self.slow_motion),
+            SettingsMenu::Fade => _on_off(self.fade),
+            SettingsMenu::ShowFps => _on_off(self.show_fps),
+            SettingsMenu::Palette => self.palette.name().to_string(),
+            SettingsMenu::Keyconfig | SettingsMenu::Back => String::new(),
+        }
+    }
+    pub fn get_mixer(&self) -> Mixer {
+        let _level = |v: i32| v as f32 / MAX_VOLUME as f32;
+        Mixer {
+            master: _level(self.volume),
+            sfx: _level(self.sfx_volume),
+            music: _level(self.music_volume),
+        }
+    }
+}
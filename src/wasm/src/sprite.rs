@@ -0,0 +1,484 @@
+use std::cell::RefCell;
+use std::f64::consts::PI;
+use std::fmt;
+use std::sync::OnceLock;
+use wasm_bindgen::JsCast;
+use web_sys::{
+    CanvasRenderingContext2d, OffscreenCanvas, OffscreenCanvasRenderingContext2d, Path2d,
+};
+
+use crate::{DEFAULT_COLOR, GREEN_DARK_LIGHT, LIGHT_GREEN_COLOR, LIGHT_YELLOR_GREEN, WHITE_COLOR};
+
+// Palette : the colors of a sprite's slots, one sprite can be drawn in many palettes
+//
+// The bullet palettes are named after their slot colors: green, pale (green) & mint.
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Palette {
+    Ship,
+    SuperShip,
+    Boss,
+    Flash, // a hit flash, all white
+    GreenPale,
+    PaleGreen,
+    Pale,
+    Mint,
+    MintPale,
+}
+
+impl Palette {
+    const ALL: [Palette; 9] = [
+        Palette::Ship,
+        Palette::SuperShip,
+        Palette::Boss,
+        Palette::Flash,
+        Palette::GreenPale,
+        Palette::PaleGreen,
+        Palette::Pale,
+        Palette::Mint,
+        Palette::MintPale,
+    ];
+    pub fn colors(&self) -> &'static [&'static str] {
+        match self {
+            Palette::Ship => &[DEFAULT_COLOR, LIGHT_GREEN_COLOR, LIGHT_GREEN_COLOR],
+            Palette::SuperShip => &[LIGHT_YELLOR_GREEN, DEFAULT_COLOR, LIGHT_GREEN_COLOR],
+            Palette::Boss => &[GREEN_DARK_LIGHT, DEFAULT_COLOR, LIGHT_GREEN_COLOR],
+            Palette::Flash => &[WHITE_COLOR, WHITE_COLOR, WHITE_COLOR],
+            Palette::GreenPale => &[DEFAULT_COLOR, LIGHT_GREEN_COLOR],
+            Palette::PaleGreen => &[LIGHT_GREEN_COLOR, DEFAULT_COLOR],
+            Palette::Pale => &[LIGHT_GREEN_COLOR],
+            Palette::Mint => &[LIGHT_YELLOR_GREEN],
+            Palette::MintPale => &[LIGHT_YELLOR_GREEN, LIGHT_GREEN_COLOR],
+        }
+    }
+}
+
+const IMAGE_PADDING: f64 = 2.0; // px around a pre-rendered sprite, strokes reach out of its size
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Paint {
+    Fill(usize),        // palette slot
+    Stroke(usize, f64), // palette slot & line width
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Segment {
+    Move(f64, f64),
+    Line(f64, f64),
+    Cubic((f64, f64), (f64, f64), (f64, f64)), // control points & end
+    Close,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Shape {
+    Polygon(Vec<(f64, f64)>),
+    Circle(f64, f64, f64), // center & radius
+    Path(Vec<Segment>),    // open unless it closes itself
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Part {
+    pub paint: Paint,
+    pub shape: Shape,
+}
+
+// Sprite : a vector image, parts painted in order from the top left corner
+//
+// Text format, one statement per line, ";" starts a comment:
+//
+//   size 42 56                     width & height
+//   fill 0 poly 17,0 25,0 25,17    a polygon filled with palette slot 0
+//   fill 1 circle 112 112 25       a circle at (112, 112) of radius 25
+//   stroke 1 2 poly 85,85 140,85   the outline of a polygon in slot 1, 2 px wide
+//   fill 2 path M 0,0 C 9,0 9,9 0,9 Z
+//                                  a path of moves, lines, cubic curves & closes
+//
+// Any paint goes with any shape.
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprite {
+    pub w: f64,
+    pub h: f64,
+    pub parts: Vec<Part>,
+}
+
+fn parse_number(token: &str) -> Result<f64, String> {
+    token
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite())
+        .ok_or(format!("bad number \"{}\"", token))
+}
+
+fn parse_slot(token: &str) -> Result<usize, String> {
+    token
+        .parse()
+        .map_err(|_| format!("bad palette slot \"{}\"", token))
+}
+
+fn parse_point(token: &str) -> Result<(f64, f64), String> {
+    let Some((_x, _y)) = token.split_once(',') else {
+        return Err(format!("bad point \"{}\"", token));
+    };
+    Ok((parse_number(_x)?, parse_number(_y)?))
+}
+
+fn parse_path(tokens: &[&str]) -> Result<Vec<Segment>, String> {
+    let mut _segments = vec![];
+    let mut i = 0;
+    while i < tokens.len() {
+        let _command = tokens[i];
+        let _count = match _command {
+            "M" | "L" => 1,
+            "C" => 3,
+            "Z" => 0,
+            _ => return Err(format!("bad path command \"{}\"", _command)),
+        };
+        let Some(_points) = tokens.get(i + 1..i + 1 + _count) else {
+            return Err(format!("{} needs {} points", _command, _count));
+        };
+        let _points = _points
+            .iter()
+            .map(|p| parse_point(p))
+            .collect::<Result<Vec<_>, _>>()?;
+        _segments.push(match (_command, &_points[..]) {
+            ("M", [_p]) => Segment::Move(_p.0, _p.1),
+            ("L", [_p]) => Segment::Line(_p.0, _p.1),
+            ("C", [_c1, _c2, _p]) => Segment::Cubic(*_c1, *_c2, *_p),
+            _ => Segment::Close,
+        });
+        i += 1 + _count;
+    }
+    match _segments.first() {
+        Some(Segment::Move(..)) => Ok(_segments),
+        _ => Err("a path starts with M".to_string()),
+    }
+}
+
+fn parse_shape(tokens: &[&str]) -> Result<Shape, String> {
+    match tokens {
+        ["poly", _points @ ..] => {
+            let _points = _points
+                .iter()
+                .map(|p| parse_point(p))
+                .collect::<Result<Vec<_>, _>>()?;
+            if _points.len() < 2 {
+                return Err("a polygon needs 2 points or more".to_string());
+            }
+            Ok(Shape::Polygon(_points))
+        }
+        ["circle", _x, _y, _r] => Ok(Shape::Circle(
+            parse_number(_x)?,
+            parse_number(_y)?,
+            parse_number(_r)?,
+        )),
+        ["path", _segments @ ..] => Ok(Shape::Path(parse_path(_segments)?)),
+        _ => Err(format!("unknown shape \"{}\"", tokens.join(" "))),
+    }
+}
+
+impl Sprite {
+    pub fn parse(text: &str) -> Result<Sprite, String> {
+        let mut _sprite = Sprite {
+            w: 0.0,
+            h: 0.0,
+            parts: vec![],
+        };
+        for (i, _line) in text.lines().enumerate() {
+            let _error = |e: String| format!("line {}: {}", i + 1, e);
+            let _line = _line.split(';').next().unwrap_or("").trim();
+            let _tokens: Vec<&str> = _line.split_whitespace().collect();
+            let (_paint, _shape_start) = match _tokens[..] {
+                [] => continue,
+                ["size", _w, _h] => {
+                    _sprite.w = parse_number(_w).map_err(_error)?;
+                    _sprite.h = parse_number(_h).map_err(_error)?;
+                    continue;
+                }
+                ["fill", _slot, ..] => (Paint::Fill(parse_slot(_slot).map_err(_error)?), 2),
+                ["stroke", _slot, _width, ..] => (
+                    Paint::Stroke(
+                        parse_slot(_slot).map_err(_error)?,
+                        parse_number(_width).map_err(_error)?,
+                    ),
+                    3,
+                ),
+                _ => return Err(_error(format!("unknown statement \"{}\"", _line))),
+            };
+            _sprite.parts.push(Part {
+                paint: _paint,
+                shape: parse_shape(&_tokens[_shape_start..]).map_err(_error)?,
+            });
+        }
+        if _sprite.w <= 0.0 || _sprite.h <= 0.0 {
+            return Err("missing size".to_string());
+        }
+        Ok(_sprite)
+    }
+
+    /**
+     * the parts traced as paths moved by (x, y), with their color & line width (None: filled)
+     */
+    fn get_paths<'a>(
+        &self,
+        x: f64,
+        y: f64,
+        colors: &[&'a str],
+    ) -> Vec<(Path2d, &'a str, Option<f64>)> {
+        let mut _paths = vec![];
+        for _part in self.parts.iter() {
+            let Ok(_path) = Path2d::new() else {
+                continue;
+            };
+            match &_part.shape {
+                Shape::Polygon(_points) => {
+                    for (i, (_px, _py)) in _points.iter().enumerate() {
+                        if i == 0 {
+                            _path.move_to(x + _px, y + _py);
+                        } else {
+                            _path.line_to(x + _px, y + _py);
+                        }
+                    }
+                }
+                Shape::Circle(_cx, _cy, _r) => {
+                    let _ = _path.arc(x + _cx, y + _cy, *_r, 0.0, 2.0 * PI);
+                }
+                Shape::Path(_segments) => {
+                    for _segment in _segments {
+                        match *_segment {
+                            Segment::Move(_px, _py) => _path.move_to(x + _px, y + _py),
+                            Segment::Line(_px, _py) => _path.line_to(x + _px, y + _py),
+                            Segment::Cubic(_c1, _c2, _p) => _path.bezier_curve_to(
+                                x + _c1.0,
+                                y + _c1.1,
+                                x + _c2.0,
+                                y + _c2.1,
+                                x + _p.0,
+                                y + _p.1,
+                            ),
+                            Segment::Close => _path.close_path(),
+                        }
+                    }
+                }
+            }
+            if !matches!(_part.shape, Shape::Path(_)) {
+                _path.close_path();
+            }
+            let (_slot, _width) = match _part.paint {
+                Paint::Fill(_slot) => (_slot, None),
+                Paint::Stroke(_slot, _width) => (_slot, Some(_width)),
+            };
+            _paths.push((_path, *colors.get(_slot).unwrap_or(&DEFAULT_COLOR), _width));
+        }
+        _paths
+    }
+
+    /**
+     * trace & paint every part, colors are the palette slots
+     */
+    pub fn draw(&self, ctx: &CanvasRenderingContext2d, x: f32, y: f32, colors: &[&str]) {
+        for (_path, _color, _width) in self.get_paths(x as f64, y as f64, colors) {
+            match _width {
+                None => {
+                    ctx.set_fill_style_str(_color);
+                    ctx.fill_with_path_2d(&_path);
+                }
+                Some(_width) => {
+                    ctx.set_stroke_style_str(_color);
+                    ctx.set_line_width(_width);
+                    ctx.stroke_with_path(&_path);
+                }
+            }
+        }
+    }
+}
+
+// the text format back, what Sprite::parse reads
+
+impl fmt::Display for Sprite {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "size {} {}", self.w, self.h)?;
+        writeln!(f)?;
+        for _part in self.parts.iter() {
+            match _part.paint {
+                Paint::Fill(_slot) => write!(f, "fill {}", _slot)?,
+                Paint::Stroke(_slot, _width) => write!(f, "stroke {} {}", _slot, _width)?,
+            }
+            match &_part.shape {
+                Shape::Polygon(_points) => {
+                    write!(f, " poly")?;
+                    for (_x, _y) in _points {
+                        write!(f, " {},{}", _x, _y)?;
+                    }
+                }
+                Shape::Circle(_x, _y, _r) => write!(f, " circle {} {} {}", _x, _y, _r)?,
+                Shape::Path(_segments) => {
+                    write!(f, " path")?;
+                    for _segment in _segments {
+                        match _segment {
+                            Segment::Move(_x, _y) => write!(f, " M {},{}", _x, _y)?,
+                            Segment::Line(_x, _y) => write!(f, " L {},{}", _x, _y)?,
+                            Segment::Cubic(_c1, _c2, _p) => write!(
+                                f,
+                                " C {},{} {},{} {},{}",
+                                _c1.0, _c1.1, _c2.0, _c2.1, _p.0, _p.1
+                            )?,
+                            Segment::Close => write!(f, " Z")?,
+                        }
+                    }
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+// Sprite Id : the built-in sprites, a character keeps one and a palette
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SpriteId {
+    Ship,
+    Boss,
+    BulletRound,
+    BulletPellet,
+    BulletOrb,
+    BulletSmall,
+    BulletSplit,
+    BulletRice,
+    BulletKnife,
+}
+
+thread_local! {
+    // pre-rendered images of the sprite & palette pairs at get_image_index, filled by prerender
+    static IMAGES: RefCell<Vec<Option<OffscreenCanvas>>> =
+        RefCell::new(vec![None; SpriteId::ALL.len() * Palette::ALL.len()]);
+}
+
+fn get_image_index(sprite: SpriteId, palette: Palette) -> usize {
+    sprite as usize * Palette::ALL.len() + palette as usize
+}
+
+/**
+ * render every sprite & palette pair the game draws once, at init: no image is created while playing
+ */
+pub fn prerender(pairs: impl IntoIterator<Item = (SpriteId, Palette)>) {
+    IMAGES.with_borrow_mut(|_images| {
+        for (_sprite, _palette) in pairs {
+            let _image = &mut _images[get_image_index(_sprite, _palette)];
+            if _image.is_none() {
+                *_image = _sprite.render(_palette);
+            }
+        }
+    });
+}
+
+impl SpriteId {
+    const ALL: [SpriteId; 9] = [
+        SpriteId::Ship,
+        SpriteId::Boss,
+        SpriteId::BulletRound,
+        SpriteId::BulletPellet,
+        SpriteId::BulletOrb,
+        SpriteId::BulletSmall,
+        SpriteId::BulletSplit,
+        SpriteId::BulletRice,
+        SpriteId::BulletKnife,
+    ];
+    fn source(&self) -> &'static str {
+        match self {
+            SpriteId::Ship => include_str!("sprites/ship.txt"),
+            SpriteId::Boss => include_str!("sprites/boss.txt"),
+            SpriteId::BulletRound => include_str!("sprites/bullet_round.txt"),
+            SpriteId::BulletPellet => include_str!("sprites/bullet_pellet.txt"),
+            SpriteId::BulletOrb => include_str!("sprites/bullet_orb.txt"),
+            SpriteId::BulletSmall => include_str!("sprites/bullet_small.txt"),
+            SpriteId::BulletSplit => include_str!("sprites/bullet_split.txt"),
+            SpriteId::BulletRice => include_str!("sprites/bullet_rice.txt"),
+            SpriteId::BulletKnife => include_str!("sprites/bullet_knife.txt"),
+        }
+    }
+    /**
+     * the parsed sprite, all of them are parsed on the first call
+     */
+    pub fn get(&self) -> &'static Sprite {
+        static SPRITES: OnceLock<Vec<Sprite>> = OnceLock::new();
+        let _sprites = SPRITES.get_or_init(|| {
+            // the sprites are built in, a parse error is a bug
+            SpriteId::ALL
+                .iter()
+                .map(|s| {
+                    Sprite::parse(s.source()).unwrap_or_else(|e| panic!("sprite {:?}: {}", s, e))
+                })
+                .collect()
+        });
+        &_sprites[*self as usize]
+    }
+    /**
+     * the sprite drawn once in the palette on an offscreen canvas, with IMAGE_PADDING around it,
+     * None where OffscreenCanvas isn't supported
+     */
+    fn render(&self, palette: Palette) -> Option<OffscreenCanvas> {
+        let _sprite = self.get();
+        let _canvas = OffscreenCanvas::new(
+            (_sprite.w + IMAGE_PADDING * 2.0).ceil() as u32,
+            (_sprite.h + IMAGE_PADDING * 2.0).ceil() as u32,
+        )
+        .ok()?;
+        let _context = _canvas
+            .get_context("2d")
+            .ok()??
+            .dyn_into::<OffscreenCanvasRenderingContext2d>()
+            .ok()?;
+        for (_path, _color, _width) in
+            _sprite.get_paths(IMAGE_PADDING, IMAGE_PADDING, palette.colors())
+        {
+            match _width {
+                None => {
+                    _context.set_fill_style_str(_color);
+                    _context.fill_with_path_2d(&_path);
+                }
+                Some(_width) => {
+                    _context.set_stroke_style_str(_color);
+                    _context.set_line_width(_width);
+                    _context.stroke_with_path(&_path);
+                }
+            }
+        }
+        Some(_canvas)
+    }
+    /**
+     * blit the pre-rendered image at whole pixels, pairs prerender didn't render are drawn as vectors
+     */
+    pub fn draw(&self, ctx: &CanvasRenderingContext2d, x: f32, y: f32, palette: Palette) {
+        IMAGES.with_borrow(|_images| match &_images[get_image_index(*self, palette)] {
+            Some(_image) => {
+                let _ = ctx.draw_image_with_offscreen_canvas(
+                    _image,
+                    (x as f64 - IMAGE_PADDING).round(),
+                    (y as f64 - IMAGE_PADDING).round(),
+                );
+            }
+            None => self.get().draw(ctx, x, y, palette.colors()),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_sprites_parse_and_print_back() {
+        for _id in SpriteId::ALL {
+            let _sprite =
+                Sprite::parse(_id.source()).unwrap_or_else(|e| panic!("sprite {:?}: {}", _id, e));
+            assert_eq!(
+                Sprite::parse(&_sprite.to_string()),
+                Ok(_sprite),
+                "{:?}",
+                _id
+            );
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use web_sys::window;
+
+/**
+ * key-value storage for saved data (key map, high scores)
+ */
+pub trait Storage: std::fmt::Debug {
+    fn load(&self, key: &str) -> Option<String>;
+    fn save(&self, key: &str, value: &str);
+}
+
+/**
+ * localStorage access, None when the browser denies storage
+ */
+fn get_local_storage() -> Option<web_sys::Storage> {
+    window()?.local_storage().ok()?
+}
+
+// browser localStorage
+
+#[derive(Debug)]
+pub struct LocalStorage;
+
+impl Storage for LocalStorage {
+    fn load(&self, key: &str) -> Option<String> {
+        get_local_storage()?.get_item(key).ok()?
+    }
+    fn save(&self, key: &str, value: &str) {
+        if let Some(_storage) = get_local_storage() {
+            let _ = _storage.set_item(key, value);
+        }
+    }
+}
+
+// in-memory storage: kept until the page closes, also for native runs and tests
+
+#[derive(Debug, Default)]
+pub struct MemoryStorage {
+    items: RefCell<HashMap<String, String>>,
+}
+
+impl Storage for MemoryStorage {
+    fn load(&self, key: &str) -> Option<String> {
+        self.items.borrow().get(key).cloned()
+    }
+    fn save(&self, key: &str, value: &str) {
+        self.items
+            .borrow_mut()
+            .insert(key.to_string(), value.to_string());
+    }
+}
+
+/**
+ * localStorage if the browser allows it, otherwise memory, always memory in native runs
+ */
+pub fn open() -> Rc<dyn Storage> {
+    if cfg!(target_arch = "wasm32") && get_local_storage().is_some() {
+        Rc::new(LocalStorage)
+    } else {
+        Rc::new(MemoryStorage::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Difficulty;
+    use crate::achievement::{Achievements, RunStats};
+    use crate::highscore::{HighScore, HighScores};
+    use crate::input::{Action, KeyMap};
+    use crate::settings::Settings;
+
+    #[test]
+    fn memory_storage_round_trips() {
+        let _storage = open();
+        assert_eq!(_storage.load("key"), None);
+        _storage.save("key", "value");
+        assert_eq!(_storage.load("key").as_deref(), Some("value"));
+        _storage.save("key", "other");
+        assert_eq!(_storage.load("key").as_deref(), Some("other"));
+    }
+
+    #[test]
+    fn saved_data_loads_back() {
+        let _storage = MemoryStorage::default();
+
+        let mut _high_scores = HighScores::default();
+        _high_scores.insert(HighScore {
+            score: 1234,
+            milli_secondtime: 56_000,
+            date: 0.0,
+            name: "TESTER".to_string(),
+            seed: 7,
+            difficulty: Difficulty::Hard,
+        });
+        _high_scores.save(&_storage);
+        assert_eq!(HighScores::load(&_storage), _high_scores);
+
+        let _settings = Settings {
+            difficulty: Difficulty::Lunatic,
+            show_fps: true,
+            ..Settings::default()
+        };
+        _settings.save(&_storage);
+        assert_eq!(Settings::load(&_storage), _settings);
+
+        let mut _key_map = KeyMap::default();
+        _key_map.bind(Action::Bomb, "KeyB");
+        _key_map.save(&_storage);
+        assert_eq!(KeyMap::load(&_storage), _key_map);
+
+        let mut _achievements = Achievements::default();
+        _achievements.evaluate(&RunStats {
+            cleared: true,
+            difficulty: Difficulty::Normal,
+            milli_secondtime: 90_000,
+            max_bullets: 0,
+            super_count: 1,
+            misses: 1,
+            graze: 0,
+        });
+        _achievements.save(&_storage);
+        assert_eq!(Achievements::load(&_storage), _achievements);
+    }
+
+    #[test]
+    fn missing_data_loads_the_defaults() {
+        let _storage = MemoryStorage::default();
+        assert_eq!(HighScores::load(&_storage), HighScores::default());
+        assert_eq!(Settings::load(&_storage), Settings::default());
+        assert_eq!(KeyMap::load(&_storage), KeyMap::default());
+        assert_eq!(Achievements::load(&_storage), Achievements::default());
+    }
+}
@@ -0,0 +1,602 @@
+pub use crate::sprite::Sprite;
+use crate::sprite::{Paint, Part, Segment, Shape};
+
+const PRECISION: f64 = 100.0; // coordinates are rounded to 1 / PRECISION px
+
+// SVG Import : simple vector drawings into sprites
+//
+// Reads <path> (M, L, H, V, C & Z, absolute or relative), <circle>, <rect> and
+// <polygon> elements with fill & stroke attributes or style properties, which
+// they inherit from their groups. Every distinct color becomes a palette slot,
+// in order of first use. Transforms, stylesheets & classes and paints other
+// than plain colors are not supported, shapes in <defs>, <clipPath> and other
+// elements that aren't drawn are skipped.
+
+const NOT_DRAWN: [&str; 6] = ["defs", "clipPath", "mask", "marker", "pattern", "symbol"];
+const NOT_COLORS: [&str; 6] = [
+    "currentColor",
+    "inherit",
+    "initial",
+    "unset",
+    "context-fill",
+    "context-stroke",
+];
+
+// Tag : an element of the document, its name & attributes
+
+#[derive(Clone, Debug, PartialEq)]
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, &'a str)>,
+    parents: Vec<usize>, // indices of the enclosing tags, outermost first
+}
+
+impl Tag<'_> {
+    fn get(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, v)| *v)
+    }
+    // a presentation attribute or its style property, style wins
+    fn get_style(&self, name: &str) -> Option<&str> {
+        let _style = self.get("style").and_then(|s| {
+            s.split(';').find_map(|_declaration| {
+                let (_name, _value) = _declaration.split_once(':')?;
+                (_name.trim() == name).then_some(_value.trim())
+            })
+        });
+        _style.or(self.get(name))
+    }
+    fn get_number(&self, name: &str) -> Result<f64, String> {
+        match self.get(name) {
+            Some(_value) => parse_number(_value.trim_end_matches("px")),
+            None => Ok(0.0),
+        }
+    }
+}
+
+fn parse_number(text: &str) -> Result<f64, String> {
+    text.trim()
+        .parse()
+        .ok()
+        .filter(|n: &f64| n.is_finite())
+        .ok_or(format!("bad number \"{}\"", text))
+}
+
+/**
+ * the opening tags of a document, comments & declarations skipped
+ */
+fn get_tags(text: &str) -> Result<Vec<Tag<'_>>, String> {
+    let mut _tags: Vec<Tag> = vec![];
+    let mut _open: Vec<usize> = vec![]; // tags not closed yet
+    let mut _rest = text;
+    while let Some(_start) = _rest.find('<') {
+        _rest = &_rest[_start + 1..];
+        if let Some(_comment) = _rest.strip_prefix("!--") {
+            let Some(_end) = _comment.find("-->") else {
+                return Err("unclosed comment".to_string());
+            };
+            _rest = &_comment[_end + 3..];
+            continue;
+        }
+        if _rest.starts_with(['?', '!', '/']) {
+            let Some(_end) = _rest.find('>') else {
+                return Err("unclosed tag".to_string());
+            };
+            if let Some(_name) = _rest[.._end].strip_prefix('/') {
+                match _open.pop() {
+                    Some(i) if _tags[i].name == _name.trim() => {}
+                    _ => return Err(format!("unexpected </{}>", _name.trim())),
+                }
+            }
+            _rest = &_rest[_end + 1..];
+            continue;
+        }
+
+        // name, then name="value" pairs until > or />
+
+        let _name_end = _rest
+            .find(|c: char| c.is_whitespace() || c == '/' || c == '>')
+            .ok_or("unclosed tag")?;
+        let mut _tag = Tag {
+            name: &_rest[.._name_end],
+            attributes: vec![],
+            parents: _open.clone(),
+        };
+        _rest = &_rest[_name_end..];
+        loop {
+            _rest = _rest.trim_start();
+            if let Some(_after) = _rest.strip_prefix("/>") {
+                _rest = _after;
+                break;
+            }
+            if let Some(_after) = _rest.strip_prefix('>') {
+                _open.push(_tags.len());
+                _rest = _after;
+                break;
+            }
+            let Some((_name, _after)) = _rest.split_once('=') else {
+                return Err(format!("bad attribute in <{}>", _tag.name));
+            };
+            let _after = _after.trim_start();
+            let Some(_quote) = _after.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                return Err(format!("unquoted attribute \"{}\"", _name.trim()));
+            };
+            let Some((_value, _after)) = _after[1..].split_once(_quote) else {
+                return Err(format!("unclosed attribute \"{}\"", _name.trim()));
+            };
+            _tag.attributes.push((_name.trim(), _value));
+            _rest = _after;
+        }
+        _tags.push(_tag);
+    }
+    Ok(_tags)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum PathToken {
+    Command(char),
+    Number(f64),
+}
+
+/**
+ * the commands & numbers of path data, "M10-5.5.5" is M 10 -5.5 0.5
+ */
+fn get_path_tokens(data: &str) -> Result<Vec<PathToken>, String> {
+    let mut _tokens = vec![];
+    let _chars: Vec<char> = data.chars().collect();
+    let mut i = 0;
+    while i < _chars.len() {
+        let _char = _chars[i];
+        if _char.is_whitespace() || _char == ',' {
+            i += 1;
+        } else if _char.is_ascii_alphabetic() && _char != 'e' && _char != 'E' {
+            _tokens.push(PathToken::Command(_char));
+            i += 1;
+        } else {
+            let _start = i;
+            let mut _dot = false;
+            let mut _exponent = false;
+            if _chars[i] == '-' || _chars[i] == '+' {
+                i += 1;
+            }
+            while i < _chars.len() {
+                match _chars[i] {
+                    '0'..='9' => {}
+                    '.' if !_dot && !_exponent => _dot = true,
+                    'e' | 'E' if !_exponent => {
+                        _exponent = true;
+                        if matches!(_chars.get(i + 1), Some('-' | '+')) {
+                            i += 1;
+                        }
+                    }
+                    _ => break,
+                }
+                i += 1;
+            }
+            let _number: String = _chars[_start..i].iter().collect();
+            _tokens.push(PathToken::Number(parse_number(&_number)?));
+        }
+    }
+    Ok(_tokens)
+}
+
+fn parse_path(data: &str) -> Result<Vec<Segment>, String> {
+    let _tokens = get_path_tokens(data)?;
+    let mut _segments = vec![];
+    let (mut _x, mut _y) = (0.0, 0.0);
+    let (mut _start_x, mut _start_y) = (0.0, 0.0);
+    let mut _command = None;
+    let mut i = 0;
+    while i < _tokens.len() {
+        // numbers after a command repeat it, after M & m they are lines
+        let _letter = match (_tokens[i], _command) {
+            (PathToken::Command(_letter), _) => {
+                i += 1;
+                _letter
+            }
+            (_, Some('M')) => 'L',
+            (_, Some('m')) => 'l',
+            (_, Some('Z' | 'z') | None) => return Err("number without a command".to_string()),
+            (_, Some(_letter)) => _letter,
+        };
+        _command = Some(_letter);
+        let _count = match _letter.to_ascii_uppercase() {
+            'M' | 'L' => 2,
+            'H' | 'V' => 1,
+            'C' => 6,
+            'Z' => 0,
+            _ => return Err(format!("unsupported path command \"{}\"", _letter)),
+        };
+        let Some(_numbers) = _tokens.get(i..i + _count) else {
+            return Err(format!("\"{}\" needs {} numbers", _letter, _count));
+        };
+        let _numbers = _numbers
+            .iter()
+            .map(|t| match t {
+                PathToken::Number(_number) => Ok(*_number),
+                PathToken::Command(_) => Err(format!("\"{}\" needs {} numbers", _letter, _count)),
+            })
+            .collect::<Result<Vec<f64>, String>>()?;
+        i += _count;
+
+        // relative coordinates are from the current point
+        let (_dx, _dy) = if _letter.is_ascii_lowercase() {
+            (_x, _y)
+        } else {
+            (0.0, 0.0)
+        };
+        let _point = |n: usize| (_dx + _numbers[n], _dy + _numbers[n + 1]);
+        let _segment = match _letter.to_ascii_uppercase() {
+            'M' => {
+                (_x, _y) = _point(0);
+                (_start_x, _start_y) = (_x, _y);
+                Segment::Move(_x, _y)
+            }
+            'L' => {
+                (_x, _y) = _point(0);
+                Segment::Line(_x, _y)
+            }
+            'H' => {
+                _x = _dx + _numbers[0];
+                Segment::Line(_x, _y)
+            }
+            'V' => {
+                _y = _dy + _numbers[0];
+                Segment::Line(_x, _y)
+            }
+            'C' => {
+                let (_c1, _c2, _p) = (_point(0), _point(2), _point(4));
+                (_x, _y) = _p;
+                Segment::Cubic(_c1, _c2, _p)
+            }
+            _ => {
+                (_x, _y) = (_start_x, _start_y);
+                Segment::Close
+            }
+        };
+        _segments.push(_segment);
+    }
+    match _segments.first() {
+        Some(Segment::Move(..)) => Ok(_segments),
+        _ => Err("a path starts with M".to_string()),
+    }
+}
+
+fn parse_points(data: &str) -> Result<Vec<(f64, f64)>, String> {
+    let _numbers = data
+        .split([' ', ',', '\t', '\n', '\r'])
+        .filter(|n| !n.is_empty())
+        .map(parse_number)
+        .collect::<Result<Vec<f64>, String>>()?;
+    if _numbers.len() % 2 != 0 || _numbers.len() < 4 {
+        return Err(format!("bad points \"{}\"", data));
+    }
+    Ok(_numbers.chunks(2).map(|p| (p[0], p[1])).collect())
+}
+
+fn get_shape(tag: &Tag) -> Result<Option<Shape>, String> {
+    let _shape = match tag.name {
+        "path" => Shape::Path(parse_path(tag.get("d").unwrap_or(""))?),
+        "circle" => Shape::Circle(
+            tag.get_number("cx")?,
+            tag.get_number("cy")?,
+            tag.get_number("r")?,
+        ),
+        "rect" => {
+            let (_x, _y) = (tag.get_number("x")?, tag.get_number("y")?);
+            let (_w, _h) = (tag.get_number("width")?, tag.get_number("height")?);
+            Shape::Polygon(vec![
+                (_x, _y),
+                (_x + _w, _y),
+                (_x + _w, _y + _h),
+                (_x, _y + _h),
+            ])
+        }
+        "polygon" => Shape::Polygon(parse_points(tag.get("points").unwrap_or(""))?),
+        _ => return Ok(None),
+    };
+    Ok(Some(_shape))
+}
+
+/**
+ * the shape moved by (dx, dy) & rounded to PRECISION
+ */
+fn get_moved(shape: Shape, dx: f64, dy: f64) -> Shape {
+    let _round = |v: f64| (v * PRECISION).round() / PRECISION;
+    let _move = |(x, y): (f64, f64)| (_round(x + dx), _round(y + dy));
+    match shape {
+        Shape::Polygon(_points) => Shape::Polygon(_points.into_iter().map(_move).collect()),
+        Shape::Circle(_x, _y, _r) => {
+            let (_x, _y) = _move((_x, _y));
+            Shape::Circle(_x, _y, _round(_r))
+        }
+        Shape::Path(_segments) => Shape::Path(
+            _segments
+                .into_iter()
+                .map(|s| match s {
+                    Segment::Move(_x, _y) => {
+                        let (_x, _y) = _move((_x, _y));
+                        Segment::Move(_x, _y)
+                    }
+                    Segment::Line(_x, _y) => {
+                        let (_x, _y) = _move((_x, _y));
+                        Segment::Line(_x, _y)
+                    }
+                    Segment::Cubic(_c1, _c2, _p) => {
+                        Segment::Cubic(_move(_c1), _move(_c2), _move(_p))
+                    }
+                    Segment::Close => Segment::Close,
+                })
+                .collect(),
+        ),
+    }
+}
+
+/**
+ * a fill or stroke value that is a plain color: gradients, patterns & keywords have no palette slot
+ */
+fn check_paint(paint: &str) -> Result<&str, String> {
+    if paint.starts_with("url(") || NOT_COLORS.iter().any(|c| c.eq_ignore_ascii_case(paint)) {
+        return Err(format!("unsupported paint \"{}\"", paint));
+    }
+    Ok(paint)
+}
+
+/**
+ * slot of a color in the palette, added if it is new
+ */
+fn get_slot(palette: &mut Vec<String>, color: &str) -> usize {
+    let _color = color.to_ascii_lowercase();
+    match palette.iter().position(|c| *c == _color) {
+        Some(_slot) => _slot,
+        None => {
+            palette.push(_color);
+            palette.len() - 1
+        }
+    }
+}
+
+/**
+ * a sprite from an SVG document & the colors of its palette slots
+ */
+pub fn import(text: &str) -> Result<(Sprite, Vec<String>), String> {
+    let _tags = get_tags(text)?;
+    let Some(_svg) = _tags.iter().find(|t| t.name == "svg") else {
+        return Err("no <svg> element".to_string());
+    };
+
+    // size & origin from the view box, or the width & height
+
+    let (_min_x, _min_y, _w, _h) = match _svg.get("viewBox") {
+        Some(_view_box) => {
+            let _numbers = _view_box
+                .split([' ', ','])
+                .filter(|n| !n.is_empty())
+                .map(parse_number)
+                .collect::<Result<Vec<f64>, String>>()?;
+            let [_min_x, _min_y, _w, _h] = _numbers[..] else {
+                return Err(format!("bad viewBox \"{}\"", _view_box));
+            };
+            (_min_x, _min_y, _w, _h)
+        }
+        None => (
+            0.0,
+            0.0,
+            _svg.get_number("width")?,
+            _svg.get_number("height")?,
+        ),
+    };
+    if _w <= 0.0 || _h <= 0.0 {
+        return Err("missing size, set a viewBox or a width & height".to_string());
+    }
+    let mut _sprite = Sprite {
+        w: _w,
+        h: _h,
+        parts: vec![],
+    };
+    let mut _palette = vec![];
+
+    // shapes, filled then stroked like SVG paints them
+
+    for _tag in _tags.iter() {
+        let _error = |e: String| format!("<{}>: {}", _tag.name, e);
+        // a stylesheet would paint the shapes some other way, even from <defs>
+        if _tag.name == "style" {
+            return Err(_error("stylesheets are not supported".to_string()));
+        }
+        let _parents: Vec<&Tag> = _tag.parents.iter().map(|i| &_tags[*i]).collect();
+        if NOT_DRAWN.contains(&_tag.name) || _parents.iter().any(|p| NOT_DRAWN.contains(&p.name)) {
+            continue;
+        }
+        if _tag.get("transform").is_some() {
+            return Err(_error("transforms are not supported".to_string()));
+        }
+        if _tag.get("class").is_some() {
+            return Err(_error("classes are not supported".to_string()));
+        }
+        let Some(_shape) = get_shape(_tag).map_err(_error)? else {
+            continue;
+        };
+        let _shape = get_moved(_shape, -_min_x, -_min_y);
+        // paint from the shape or its nearest group that sets it
+        let _get_paint = |name: &str| {
+            _tag.get_style(name)
+                .or_else(|| _parents.iter().rev().find_map(|p| p.get_style(name)))
+        };
+        // the SVG default is a black fill & no stroke
+        let _fill = check_paint(_get_paint("fill").unwrap_or("#000000")).map_err(_error)?;
+        if _fill != "none" {
+            _sprite.parts.push(Part {
+                paint: Paint::Fill(get_slot(&mut _palette, _fill)),
+                shape: _shape.clone(),
+            });
+        }
+        if let Some(_stroke) = _get_paint("stroke").filter(|s| *s != "none") {
+            let _stroke = check_paint(_stroke).map_err(_error)?;
+            let _width = match _get_paint("stroke-width") {
+                Some(_width) => parse_number(_width.trim_end_matches("px")).map_err(_error)?,
+                None => 1.0,
+            };
+            _sprite.parts.push(Part {
+                paint: Paint::Stroke(get_slot(&mut _palette, _stroke), _width),
+                shape: _shape,
+            });
+        }
+    }
+    if _sprite.parts.is_empty() {
+        return Err("no shapes".to_string());
+    }
+    Ok((_sprite, _palette))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import_body(body: &str) -> Result<(Sprite, Vec<String>), String> {
+        import(&format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 20 20\">{}</svg>",
+            body
+        ))
+    }
+
+    #[test]
+    fn path_numbers_split_on_signs_and_second_dots() {
+        assert_eq!(
+            get_path_tokens("M10-5.5.5").unwrap(),
+            [
+                PathToken::Command('M'),
+                PathToken::Number(10.0),
+                PathToken::Number(-5.5),
+                PathToken::Number(0.5),
+            ]
+        );
+        assert_eq!(
+            get_path_tokens("1e-2,2E1").unwrap(),
+            [PathToken::Number(0.01), PathToken::Number(20.0)]
+        );
+    }
+
+    #[test]
+    fn path_relative_and_axis_commands() {
+        assert_eq!(
+            parse_path("M1 2 h3 v4 H0 V0 c1 1 2 2 3 3 z l1 1").unwrap(),
+            [
+                Segment::Move(1.0, 2.0),
+                Segment::Line(4.0, 2.0),
+                Segment::Line(4.0, 6.0),
+                Segment::Line(0.0, 6.0),
+                Segment::Line(0.0, 0.0),
+                Segment::Cubic((1.0, 1.0), (2.0, 2.0), (3.0, 3.0)),
+                Segment::Close,
+                Segment::Line(2.0, 3.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn path_numbers_repeat_the_command() {
+        assert_eq!(
+            parse_path("m1 1 2 2 L5 5 6 6 h1 1").unwrap(),
+            [
+                Segment::Move(1.0, 1.0),
+                Segment::Line(3.0, 3.0),
+                Segment::Line(5.0, 5.0),
+                Segment::Line(6.0, 6.0),
+                Segment::Line(7.0, 6.0),
+                Segment::Line(8.0, 6.0),
+            ]
+        );
+        assert_eq!(
+            parse_path("M0 0 C1 1 2 2").unwrap_err(),
+            "\"C\" needs 6 numbers"
+        );
+        assert_eq!(parse_path("L1 1").unwrap_err(), "a path starts with M");
+        assert_eq!(
+            parse_path("M0 0 A1 1 0 0 0 2 2").unwrap_err(),
+            "unsupported path command \"A\""
+        );
+    }
+
+    #[test]
+    fn shapes_inherit_the_paint_of_their_groups() {
+        let (_sprite, _palette) = import_body(
+            "<g fill=\"#ff0000\" style=\"stroke: #00ff00\" stroke-width=\"2\">\
+               <g><rect width=\"4\" height=\"4\"/></g>\
+               <circle cx=\"5\" cy=\"5\" r=\"2\" fill=\"#0000ff\" stroke=\"none\"/>\
+             </g>\
+             <circle cx=\"5\" cy=\"5\" r=\"1\"/>",
+        )
+        .unwrap();
+        assert_eq!(_palette, ["#ff0000", "#00ff00", "#0000ff", "#000000"]);
+        let _paints: Vec<Paint> = _sprite.parts.iter().map(|p| p.paint).collect();
+        assert_eq!(
+            _paints,
+            [
+                Paint::Fill(0),
+                Paint::Stroke(1, 2.0),
+                Paint::Fill(2),
+                Paint::Fill(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn shapes_that_are_not_drawn_are_skipped() {
+        let (_sprite, _) = import_body(
+            "<defs><circle cx=\"1\" cy=\"1\" r=\"1\"/></defs>\
+             <clipPath id=\"clip\"><rect width=\"2\" height=\"2\" transform=\"scale(2)\"/></clipPath>\
+             <circle cx=\"5\" cy=\"5\" r=\"2\"/>",
+        )
+        .unwrap();
+        assert_eq!(_sprite.parts.len(), 1);
+    }
+
+    #[test]
+    fn transforms_and_bad_nesting_are_rejected() {
+        assert_eq!(
+            import_body("<g transform=\"scale(2)\"><rect width=\"2\" height=\"2\"/></g>")
+                .unwrap_err(),
+            "<g>: transforms are not supported"
+        );
+        assert_eq!(
+            import_body("<g><rect width=\"2\" height=\"2\"/></svg>").unwrap_err(),
+            "unexpected </svg>"
+        );
+    }
+
+    #[test]
+    fn paints_that_are_not_colors_are_rejected() {
+        assert_eq!(
+            import_body("<rect width=\"2\" height=\"2\" fill=\"url(#gradient)\"/>").unwrap_err(),
+            "<rect>: unsupported paint \"url(#gradient)\""
+        );
+        assert_eq!(
+            import_body("<g stroke=\"currentColor\"><circle cx=\"1\" cy=\"1\" r=\"1\"/></g>")
+                .unwrap_err(),
+            "<circle>: unsupported paint \"currentColor\""
+        );
+        assert_eq!(
+            import_body("<circle cx=\"1\" cy=\"1\" r=\"1\" stroke=\"red\" stroke-width=\"thin\"/>")
+                .unwrap_err(),
+            "<circle>: bad number \"thin\""
+        );
+    }
+
+    #[test]
+    fn stylesheets_and_classes_are_rejected() {
+        assert_eq!(
+            import_body(
+                "<defs><style>.a { fill: red; }</style></defs><rect width=\"2\" height=\"2\"/>"
+            )
+            .unwrap_err(),
+            "<style>: stylesheets are not supported"
+        );
+        assert_eq!(
+            import_body("<rect class=\"a\" width=\"2\" height=\"2\"/>").unwrap_err(),
+            "<rect>: classes are not supported"
+        );
+    }
+}
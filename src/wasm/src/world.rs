@@ -0,0 +1,929 @@
+use rand::prelude::*;
+use std::cmp::max;
+use std::f64::consts::PI;
+
+use crate::achievement::RunStats;
+use crate::input::{Action, InputState};
+use crate::particle::{
+    BOSS_EXPLOSION, BOSS_HIT, BOSS_LAST_EXPLOSION, BULLET_CANCEL, Particles, SHIP_EXPLOSION,
+};
+use crate::replay::Replay;
+use crate::settings::ControlScheme;
+use crate::sprite::{Palette, SpriteId};
+use crate::{
+    Aim, BOSS_EXPLOSION_INTERVAL, BOSS_EXPLOSION_TIME, BOSS_HEIGHT, BOSS_MAX_HP, BOSS_WIDTH,
+    BULLET_PATTERNS, Boss, Bullet, BulletPattern, BulletType, CANVAS_HEIGHT, CANVAS_WIDTH,
+    Character, CharacterShip, Difficulty, FPS, GRAZE_DISTANCE, HOMING_DAMAGE, HOMING_HEIGHT,
+    HOMING_INTERVAL, HOMING_SPEED, HOMING_WIDTH, LASER_DAMAGE, Laser, RANK_DECAY, RANK_PER_DAMAGE,
+    RANK_PER_TICK, RANK_PRESSURE, SCORE_PER_DAMAGE, SCORE_PER_GRAZE, SCORE_PER_SECOND,
+    SCORE_TIME_LIMIT, SHIP_HEIGHT, SHIP_STEP, SHIP_WIDTH, SHOT_HEIGHT, SHOT_SPEED, SHOT_WIDTH,
+    SNIPE_ACCELERATION, SNIPE_MAX_SPEED, SPLIT_NUMBER, SPLIT_SPEED, SUPER_CHARGE_DAMAGE,
+    SUPER_CHARGE_GRAZE, SUPER_CHARGE_IDLE, SUPER_FLASH_TIME, SUPER_GAUGE_MAX, SUPER_SHOT_DAMAGE,
+    SUPER_SHOT_HEIGHT, SUPER_SHOT_HP, SUPER_SHOT_WIDTH, SUPER_TIME, Ship, Shot, ShotType, get_aim,
+    get_lead_aim, get_nearest,
+};
+
+// Super Mode
+//
+// Charging --(gauge full)--> Ready --(activate_super)--> Active --(time over)--> Charging
+//
+// The gauge fills from idling (SUPER_CHARGE_IDLE per tick not shooting), grazing
+// (SUPER_CHARGE_GRAZE per bullet) and damage dealt (SUPER_CHARGE_DAMAGE per boss hp).
+// While active for SUPER_TIME ticks, the ship fires super shots instead of its weapon:
+// SUPER_SHOT_WIDTH wide, each dealing SUPER_SHOT_DAMAGE to a bullet or the boss per hit,
+// and piercing until SUPER_SHOT_HP hits.
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SuperMode {
+    Charging(i32), // gauge
+    Ready,
+    Active(i32), // remaining ticks
+}
+
+impl SuperMode {
+    pub fn charge(&mut self, energy: i32) -> bool {
+        if let SuperMode::Charging(_gauge) = self {
+            *_gauge += energy;
+            if *_gauge >= SUPER_GAUGE_MAX {
+                *self = SuperMode::Ready;
+                return true;
+            }
+        }
+        false
+    }
+    pub fn activate(&mut self) -> bool {
+        if *self == SuperMode::Ready {
+            *self = SuperMode::Active(SUPER_TIME);
+            return true;
+        }
+        false
+    }
+    pub fn update(&mut self) {
+        if let SuperMode::Active(_time) = self {
+            *_time -= 1;
+            if *_time <= 0 {
+                *self = SuperMode::Charging(0);
+            }
+        }
+    }
+    pub fn is_active(&self) -> bool {
+        matches!(self, SuperMode::Active(_))
+    }
+    // gauge ratio 0.0 - 1.0, counts down while active
+    pub fn get_ratio(&self) -> f32 {
+        match self {
+            SuperMode::Charging(_gauge) => *_gauge as f32 / SUPER_GAUGE_MAX as f32,
+            SuperMode::Ready => 1.0,
+            SuperMode::Active(_time) => *_time as f32 / SUPER_TIME as f32,
+        }
+    }
+}
+
+// Stage
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stage {
+    Openning,
+    Settings,
+    Keyconfig,
+    Highscore,
+    Achievements,
+    Playing,
+    Paused,
+    Gameover,
+    Gameclear,
+}
+
+// Pause Menu
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PauseMenu {
+    Resume,
+    Restart,
+    Settings,
+    Quit,
+}
+
+impl PauseMenu {
+    pub const ALL: [PauseMenu; 4] = [
+        PauseMenu::Resume,
+        PauseMenu::Restart,
+        PauseMenu::Settings,
+        PauseMenu::Quit,
+    ];
+    pub fn name(&self) -> &str {
+        match self {
+            PauseMenu::Resume => "Resume",
+            PauseMenu::Restart => "Restart",
+            PauseMenu::Settings => "Settings",
+            PauseMenu::Quit => "Quit to title",
+        }
+    }
+}
+
+// Menu Action : a title or pause menu item, the front end resolves clicks into them
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MenuAction {
+    Difficulty(Difficulty),
+    ToggleRank,
+    Settings,
+    Highscore,
+    Achievements,
+    Start,
+    Pause(PauseMenu),
+}
+
+// Game Event : what the simulation tells the outside (sound, clock)
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameEvent {
+    Start,
+    SuperReady,
+    SuperActivated,
+    ShotFired,
+    BulletCancel,
+    BossHit,
+    BossDefeated,
+    Clear,
+    Gameover,
+    Abandoned(RunStats), // quit or restarted from the pause menu, the stats of the run left
+}
+
+// World : the whole simulation state, stepped once per tick by an InputState
+//
+// It doesn't touch the DOM, so it runs headless for replays, bots and tests.
+// Random numbers come from a seeded generator: the same seed and inputs give the same game.
+// Trigonometry goes through libm: std's f32 functions differ between wasm and native,
+// and the leaderboard server replays runs natively.
+
+#[derive(Debug, Clone)]
+pub struct World {
+    pub stage: Stage,
+    pub shooting: bool,
+    pub pause_cursor: usize, // selected PauseMenu item
+    menu_back: Stage,        // where the settings screen returns to
+    pub difficulty: Difficulty,
+    pub rank_enabled: bool,
+    pub control: ControlScheme,
+    pub rank: f32, // 0.0 - 1.0, raises the pressure as the player performs well
+    pub weapon: ShotType,
+    pub shot_interval: i32,
+    pub laser: Option<Laser>,
+    pub super_mode: SuperMode,
+    pub super_flash: i32,
+    pub graze: i32,
+    pub max_bullets: usize, // most bullets on screen at once this run
+    pub super_count: i32,   // super mode activations this run
+    pub misses: i32,        // hits taken this run
+    pub ship: Ship,
+    pub bosses: Vec<Boss>,
+    pub bullets: Vec<Bullet>,
+    pub shots: Vec<Shot>,
+    pub particles: Particles,
+    pub boss_explosion: Option<(i32, Boss)>, // ticks left & the defeated boss, the clear follows
+    pub tick: i32,                           // ticks played, paused ticks don't count
+    pub score: i32,
+    pub seed: u64, // seed of the current run, the same seed and inputs replay it
+    pub replay: Replay,
+    rng: StdRng,
+    events: Vec<GameEvent>,
+}
+
+impl World {
+    pub fn new(seed: u64) -> Self {
+        World {
+            stage: Stage::Openning,
+            shooting: false,
+            pause_cursor: 0,
+            menu_back: Stage::Openning,
+            difficulty: Difficulty::Normal,
+            rank_enabled: false,
+            control: ControlScheme::Toggle,
+            rank: 0.0,
+            weapon: ShotType::Normal,
+            shot_interval: 0,
+            laser: None,
+            super_mode: SuperMode::Charging(0),
+            super_flash: 0,
+            graze: 0,
+            max_bullets: 0,
+            super_count: 0,
+            misses: 0,
+            ship: Ship {
+                x: CANVAS_WIDTH / 2.0 - SHIP_WIDTH / 2.0,
+                y: CANVAS_HEIGHT - SHIP_HEIGHT - 10.0,
+                dx: SHIP_STEP,
+                dy: 0.0,
+                w: SHIP_WIDTH,
+                h: SHIP_HEIGHT,
+                hp: 1,
+                vx: 0.0,
+                vy: 0.0,
+                mouse_x: CANVAS_WIDTH / 2.0,
+                axis_x: 0.0,
+                axis_y: 0.0,
+                focus: false,
+                drag_x: 0.0,
+                drag_y: 0.0,
+                t: ShotType::Normal,
+                sprite: SpriteId::Ship,
+                palette: Palette::Ship,
+            },
+            bosses: vec![Boss {
+                x: 180.0,
+                y: 60.0,
+                dx: 1.0,
+                dy: 0.0,
+                w: BOSS_WIDTH,
+                h: BOSS_HEIGHT,
+                hp: BOSS_MAX_HP,
+                max_hp: BOSS_MAX_HP,
+                d: 1.0,
+                sprite: SpriteId::Boss,
+                palette: Palette::Boss,
+            }],
+            bullets: vec![],
+            shots: vec![],
+            particles: Particles::new(seed),
+            boss_explosion: None,
+            tick: 0,
+            score: 0,
+            seed,
+            replay: Replay::new(seed, Difficulty::Normal, false, ControlScheme::Toggle),
+            rng: StdRng::seed_from_u64(seed),
+            events: vec![],
+        }
+    }
+
+    // back to the title, the menu selection and the random sequence carry over
+
+    pub fn reset(&mut self) {
+        *self = World {
+            difficulty: self.difficulty,
+            rank_enabled: self.rank_enabled,
+            control: self.control,
+            rng: self.rng.clone(),
+            ..World::new(0)
+        };
+    }
+
+    // game clock from simulation ticks, the same on every machine and in replays
+
+    pub fn get_milli_secondtime(&self) -> i32 {
+        self.tick * FPS
+    }
+
+    // how far the stage is, 0.0 - 1.0 from the damage dealt to the bosses
+
+    pub fn get_progress(&self) -> f32 {
+        let (_hp, _max_hp) = self.bosses.iter().fold((0, 0), |(_hp, _max_hp), b| {
+            (_hp + b.hp.max(0), _max_hp + b.max_hp)
+        });
+        if _max_hp == 0 {
+            return 1.0;
+        }
+        1.0 - _hp as f32 / _max_hp as f32
+    }
+
+    // what the run did so far, for achievements
+
+    pub fn get_run_stats(&self) -> RunStats {
+        RunStats {
+            cleared: matches!(self.stage, Stage::Gameclear),
+            difficulty: self.difficulty,
+            milli_secondtime: self.get_milli_secondtime(),
+            max_bullets: self.max_bullets,
+            super_count: self.super_count,
+            misses: self.misses,
+            graze: self.graze,
+        }
+    }
+
+    // events since the last call
+
+    pub fn take_events(&mut self) -> Vec<GameEvent> {
+        std::mem::take(&mut self.events)
+    }
+
+    /**
+     * advance one tick
+     */
+    pub fn step(&mut self, input: &InputState) {
+        match &self.stage {
+            Stage::Openning => {
+                if input.is_pressed(Action::Shoot) {
+                    self.proceed();
+                }
+            }
+            Stage::Gameclear | Stage::Gameover => {
+                self.particles.update();
+                if input.click.is_some() || input.is_pressed(Action::Shoot) {
+                    self.proceed();
+                }
+            }
+            // the key config & high score screens belong to the front end
+            Stage::Settings | Stage::Keyconfig | Stage::Highscore | Stage::Achievements => {}
+            Stage::Playing => self.update_playing(input),
+            Stage::Paused => self.update_paused(input),
+        }
+    }
+
+    // pause from outside the tick, e.g. when the page loses focus
+
+    pub fn pause(&mut self) {
+        if let Stage::Playing = self.stage {
+            self.stage = Stage::Paused;
+            self.pause_cursor = 0;
+        }
+    }
+
+    // the settings screen was opened from the pause menu
+
+    pub fn is_run_paused(&self) -> bool {
+        matches!(self.menu_back, Stage::Paused)
+    }
+
+    // leave the settings screen
+
+    pub fn close_settings(&mut self) {
+        self.stage = std::mem::replace(&mut self.menu_back, Stage::Openning);
+    }
+
+    // pause menu: cursor & Shoot
+
+    fn update_paused(&mut self, input: &InputState) {
+        if input.is_pressed(Action::Pause) {
+            self.stage = Stage::Playing;
+            return;
+        }
+        let _len = PauseMenu::ALL.len();
+        if input.is_pressed(Action::Up) {
+            self.pause_cursor = (self.pause_cursor + _len - 1) % _len;
+        }
+        if input.is_pressed(Action::Down) {
+            self.pause_cursor = (self.pause_cursor + 1) % _len;
+        }
+        if input.is_pressed(Action::Shoot) {
+            self.select_pause_menu(PauseMenu::ALL[self.pause_cursor]);
+        }
+    }
+
+    fn select_pause_menu(&mut self, item: PauseMenu) {
+        match item {
+            PauseMenu::Resume => self.stage = Stage::Playing,
+            PauseMenu::Restart => {
+                let _run = self.get_run_stats();
+                self.reset();
+                self.events.push(GameEvent::Abandoned(_run));
+                self.start();
+            }
+            PauseMenu::Settings => {
+                self.menu_back = Stage::Paused;
+                self.stage = Stage::Settings;
+            }
+            PauseMenu::Quit => {
+                let _run = self.get_run_stats();
+                self.reset();
+                self.events.push(GameEvent::Abandoned(_run));
+            }
+        }
+    }
+
+    /**
+     * act on a menu item instead of a tick, items of another screen are ignored
+     */
+    pub fn select_menu(&mut self, action: MenuAction) {
+        match (&self.stage, action) {
+            (Stage::Openning, MenuAction::Difficulty(_difficulty)) => self.difficulty = _difficulty,
+            (Stage::Openning, MenuAction::ToggleRank) => self.rank_enabled = !self.rank_enabled,
+            (Stage::Openning, MenuAction::Settings) => {
+                self.menu_back = Stage::Openning;
+                self.stage = Stage::Settings;
+            }
+            (Stage::Openning, MenuAction::Highscore) => self.stage = Stage::Highscore,
+            (Stage::Openning, MenuAction::Achievements) => self.stage = Stage::Achievements,
+            (Stage::Openning, MenuAction::Start) => self.start(),
+            (Stage::Paused, MenuAction::Pause(_item)) => self.select_pause_menu(_item),
+            _ => {}
+        }
+    }
+
+    // leave the title or result screen
+
+    fn proceed(&mut self) {
+        match self.stage {
+            Stage::Openning => self.start(),
+            Stage::Gameover | Stage::Gameclear => self.reset(),
+            _ => {}
+        }
+    }
+
+    // start playing with the selected difficulty
+
+    fn start(&mut self) {
+        let _seed = self.rng.r#gen();
+        self.start_with_seed(_seed);
+    }
+
+    // start a run from a known seed, replays start here
+
+    pub fn start_with_seed(&mut self, seed: u64) {
+        self.stage = Stage::Playing;
+        self.seed = seed;
+        self.rng = StdRng::seed_from_u64(seed);
+        self.particles = Particles::new(seed);
+        self.replay = Replay::new(seed, self.difficulty, self.rank_enabled, self.control);
+        self.bosses.iter_mut().for_each(|b| {
+            b.hp = self.difficulty.boss_hp();
+            b.max_hp = self.difficulty.boss_hp();
+        });
+        self.events.push(GameEvent::Start);
+    }
+
+    // bullet spawn rate & speed multiplier from difficulty and rank
+
+    fn get_pressure(&self) -> f32 {
+        if self.rank_enabled {
+            1.0 + self.rank * RANK_PRESSURE
+        } else {
+            1.0
+        }
+    }
+
+    // number of times a pattern fires on this tick, spawn rate may exceed 1 per tick
+
+    fn get_fire_count(&self, pattern: &BulletPattern) -> i32 {
+        let _rate = self.difficulty.spawn_rate() * self.get_pressure() / pattern.interval as f32;
+        ((self.tick as f32 * _rate).floor() - ((self.tick - 1) as f32 * _rate).floor()) as i32
+    }
+
+    // fire a bullet pattern from the center of the boss
+
+    fn fire_pattern(&mut self, pattern: &BulletPattern) {
+        let _x = self.bosses[0].x + BOSS_WIDTH / 2.0;
+        let _y = self.bosses[0].y + BOSS_HEIGHT / 2.0;
+        let _target_x = self.ship.x + SHIP_WIDTH / 2.0;
+        let _target_y = self.ship.y + SHIP_HEIGHT / 2.0;
+        let (_dx, _dy) = match pattern.aim {
+            Aim::Random => {
+                let _dx = (self.rng.gen_range(0..1000) as f32 / 1000.0 - 0.5) * 2.0 * pattern.speed;
+                let _dy = (self.rng.gen_range(0..1000) as f32 / 1000.0 - 0.5) * 2.0 * pattern.speed;
+                (_dx, _dy)
+            }
+            Aim::Ship | Aim::Snipe => get_aim(_x, _y, _target_x, _target_y, pattern.speed),
+            Aim::Lead => get_lead_aim(
+                _x,
+                _y,
+                _target_x,
+                _target_y,
+                self.ship.vx,
+                self.ship.vy,
+                pattern.speed,
+            ),
+        };
+        let _angle = libm::atan2f(_dy, _dx);
+        let _speed = libm::hypotf(_dx, _dy) * self.difficulty.speed() * self.get_pressure();
+        for i in 0..pattern.ways {
+            let _a = _angle + (i as f32 - (pattern.ways - 1) as f32 / 2.0) * pattern.spread;
+            let mut _bullet = Bullet::new(
+                pattern.t,
+                _x,
+                _y,
+                libm::cosf(_a) * _speed,
+                libm::sinf(_a) * _speed,
+            );
+            if let Aim::Snipe = pattern.aim {
+                let _acceleration = SNIPE_ACCELERATION * self.difficulty.speed();
+                _bullet.ax = libm::cosf(_a) * _acceleration;
+                _bullet.ay = libm::sinf(_a) * _acceleration;
+                _bullet.max_speed = SNIPE_MAX_SPEED * self.difficulty.speed() * self.get_pressure();
+            }
+            self.spawn_bullet(_bullet);
+        }
+    }
+
+    // spawn a bullet unless the screen is full
+
+    fn spawn_bullet(&mut self, bullet: Bullet) {
+        if self.bullets.len() >= self.difficulty.max_bullets() {
+            return;
+        }
+        self.bullets.push(bullet);
+    }
+
+    fn switch_weapon(&mut self) {
+        self.weapon = self.weapon.next_weapon();
+        self.laser = None;
+    }
+
+    fn activate_super(&mut self) {
+        if self.super_mode.activate() {
+            self.ship.t = ShotType::Super;
+            self.ship.palette = Palette::SuperShip;
+            self.super_flash = SUPER_FLASH_TIME;
+            self.super_count += 1;
+            self.events.push(GameEvent::SuperActivated);
+        }
+    }
+
+    fn update_playing(&mut self, input: &InputState) {
+        if input.is_pressed(Action::Pause) {
+            self.pause();
+            return;
+        }
+        self.replay.record(input);
+        if self.boss_explosion.is_some() {
+            self.update_boss_explosion();
+            return;
+        }
+        if let Some(_x) = input.pointer_x {
+            self.ship.set_mouse_x(_x);
+        }
+        if input.click.is_some() && self.control == ControlScheme::Toggle {
+            self.shooting = !self.shooting;
+        }
+        if input.is_pressed(Action::Weapon) {
+            self.switch_weapon();
+        }
+        // count down before activating, so a super lasts the full SUPER_TIME
+        self.super_mode.update();
+        if input.is_pressed(Action::Bomb) {
+            self.activate_super();
+        }
+        self.ship
+            .set_axis(input.axis_x, input.axis_y, input.is_held(Action::Focus));
+        self.ship.add_drag(input.drag_x, input.drag_y);
+        let _shooting = match self.control {
+            ControlScheme::Toggle => self.shooting || input.is_held(Action::Shoot),
+            ControlScheme::Hold => input.is_held(Action::Shoot),
+            ControlScheme::Auto => true,
+        };
+
+        // bullet create
+
+        self.tick += 1;
+        for _pattern in BULLET_PATTERNS.iter() {
+            for _ in 0..self.get_fire_count(_pattern) {
+                self.fire_pattern(_pattern);
+            }
+        }
+
+        // bullet update
+        self.bullets.iter_mut().for_each(|b| b.update());
+        let _splits: Vec<(f32, f32)> = self
+            .bullets
+            .iter()
+            .filter(|b| b.expired() && b.t == BulletType::Split)
+            .map(|b| b.get_center())
+            .collect();
+        for (_cx, _cy) in _splits {
+            for i in 0..SPLIT_NUMBER {
+                let _angle = 2.0 * PI as f32 * i as f32 / SPLIT_NUMBER as f32;
+                self.spawn_bullet(Bullet::new(
+                    BulletType::Pellet,
+                    _cx,
+                    _cy,
+                    libm::cosf(_angle) * SPLIT_SPEED,
+                    libm::sinf(_angle) * SPLIT_SPEED,
+                ));
+            }
+        }
+        self.bullets
+            .retain(|b| !b.exit() && b.hp > 0 && !b.expired());
+
+        // shot create
+
+        let _shot_count = self.shots.len();
+        if _shooting {
+            let _x = self.ship.x + SHIP_WIDTH / 2.0;
+            let _y = self.ship.y;
+            if self.super_mode.is_active() {
+                let _shot = Shot {
+                    x: _x - SUPER_SHOT_WIDTH / 2.0,
+                    y: _y,
+                    dx: 0.0,
+                    dy: SHOT_SPEED,
+                    w: SUPER_SHOT_WIDTH,
+                    h: SUPER_SHOT_HEIGHT,
+                    hp: SUPER_SHOT_HP,
+                    t: ShotType::Super,
+                };
+                self.shots.push(_shot);
+            } else if self.weapon == ShotType::Homing {
+                if self.shot_interval <= 0 {
+                    let _shot = Shot {
+                        x: _x - HOMING_WIDTH / 2.0,
+                        y: _y,
+                        dx: 0.0,
+                        dy: HOMING_SPEED,
+                        w: HOMING_WIDTH,
+                        h: HOMING_HEIGHT,
+                        hp: 1,
+                        t: ShotType::Homing,
+                    };
+                    self.shots.push(_shot);
+                    self.shot_interval = HOMING_INTERVAL;
+                }
+            } else if self.weapon == ShotType::Laser {
+                self.laser = Some(Laser::cast(_x, _y, &self.bosses));
+            } else {
+                let _shot = Shot {
+                    x: _x,
+                    y: _y,
+                    dx: 0.0,
+                    dy: SHOT_SPEED,
+                    w: SHOT_WIDTH,
+                    h: SHOT_HEIGHT,
+                    hp: 1,
+                    t: ShotType::Normal,
+                };
+                self.shots.push(_shot);
+            }
+        }
+        if !_shooting || self.super_mode.is_active() || self.weapon != ShotType::Laser {
+            self.laser = None;
+        }
+        if self.shots.len() > _shot_count {
+            self.events.push(GameEvent::ShotFired);
+        }
+        self.shot_interval -= 1;
+
+        // shots update
+
+        self.shots.retain(|s| !s.exit() && s.hp > 0);
+        let _targets: Vec<(f32, f32)> = self
+            .bosses
+            .iter()
+            .map(|b| (b.x + b.w / 2.0, b.y + b.h / 2.0))
+            .collect();
+        self.shots.iter_mut().for_each(|s| {
+            if s.t == ShotType::Homing {
+                s.steer(get_nearest(&_targets, s.x + s.w / 2.0, s.y + s.h / 2.0));
+            }
+            s.update()
+        });
+
+        // hit check bullets x ship
+
+        self.bullets.iter_mut().for_each(|b| {
+            if self.ship.hit(b) {
+                self.ship.hp -= 1;
+                self.misses += 1;
+            }
+        });
+        if self.ship.hp <= 0 {
+            self.stage = Stage::Gameover;
+            self.particles.emit(
+                &SHIP_EXPLOSION,
+                self.ship.x + self.ship.w / 2.0,
+                self.ship.y + self.ship.h / 2.0,
+            );
+            self.events.push(GameEvent::Gameover);
+        }
+
+        // graze check bullets x ship
+
+        let mut _grazed = 0;
+        self.bullets.iter_mut().for_each(|b| {
+            if !b.grazed && self.ship.near(b, GRAZE_DISTANCE) {
+                b.grazed = true;
+                _grazed += 1;
+            }
+        });
+        self.graze += _grazed;
+
+        // hit check shots x bullets (homing missiles fly over bullets)
+
+        self.shots.iter_mut().for_each(|s| {
+            if s.t == ShotType::Homing {
+                return;
+            }
+            self.bullets.iter_mut().for_each(|b| {
+                if s.hit(b) {
+                    s.hp -= 1;
+                    b.hp -= 1;
+                }
+            })
+        });
+
+        // hit check shots x boss
+
+        let _boss_hp: i32 = self.bosses.iter().map(|b| b.hp).sum();
+
+        self.shots.iter_mut().for_each(|s| {
+            self.bosses.iter_mut().for_each(|b| {
+                if s.hit(b) {
+                    self.particles.emit(&BOSS_HIT, s.x + s.w / 2.0, s.y);
+                    match s.t {
+                        ShotType::Homing => {
+                            s.hp = 0;
+                            b.hp -= HOMING_DAMAGE;
+                        }
+                        ShotType::Super => {
+                            s.hp -= 1;
+                            b.hp -= SUPER_SHOT_DAMAGE;
+                        }
+                        _ => {
+                            s.hp -= 1;
+                            b.hp -= 1;
+                        }
+                    }
+                }
+            })
+        });
+
+        // hit check laser x bullets, laser x boss (damage per tick)
+
+        if let Some(_laser) = &self.laser {
+            self.bullets.iter_mut().for_each(|b| {
+                if _laser.hit(b) {
+                    b.hp -= LASER_DAMAGE;
+                }
+            });
+            if let Some(i) = _laser.target {
+                self.bosses[i].hp -= LASER_DAMAGE;
+                self.particles
+                    .emit(&BOSS_HIT, _laser.x + _laser.w / 2.0, _laser.y);
+            }
+        }
+
+        // rank
+
+        let _damage = _boss_hp - self.bosses.iter().map(|b| b.hp).sum::<i32>();
+        if _damage > 0 {
+            self.events.push(GameEvent::BossHit);
+        }
+        if self.bullets.iter().any(|b| b.hp <= 0) {
+            self.events.push(GameEvent::BulletCancel);
+        }
+        for b in self.bullets.iter().filter(|b| b.hp <= 0) {
+            let (_x, _y) = b.get_center();
+            self.particles.emit(&BULLET_CANCEL, _x, _y);
+        }
+        if self.rank_enabled {
+            self.rank += _damage as f32 * RANK_PER_DAMAGE + RANK_PER_TICK;
+            if !_shooting {
+                self.rank -= RANK_DECAY;
+            }
+            self.rank = self.rank.clamp(0.0, 1.0);
+        }
+
+        // ship update
+
+        self.ship.update();
+
+        // boss update
+
+        self.bosses[0].update();
+
+        if self.bosses[0].hp <= 0 {
+            let _boss = self.bosses[0].clone();
+            self.bosses.retain(|s| s.hp > 0);
+            // Game Clear, after the boss explodes
+            if self.bosses.is_empty() {
+                self.score += max(0, SCORE_TIME_LIMIT - self.get_milli_secondtime()) / 1000
+                    * SCORE_PER_SECOND;
+                self.bullets.iter().for_each(|b| {
+                    let (_x, _y) = b.get_center();
+                    self.particles.emit(&BULLET_CANCEL, _x, _y);
+                });
+                self.bullets.clear();
+                self.shots.clear();
+                self.laser = None;
+                self.particles.emit(
+                    &BOSS_LAST_EXPLOSION,
+                    _boss.x + _boss.w / 2.0,
+                    _boss.y + _boss.h / 2.0,
+                );
+                self.boss_explosion = Some((BOSS_EXPLOSION_TIME, _boss));
+                self.events.push(GameEvent::BossDefeated);
+            }
+        }
+
+        // score
+
+        self.score += _damage * SCORE_PER_DAMAGE + _grazed * SCORE_PER_GRAZE;
+
+        // super mode
+
+        let mut _energy = _grazed * SUPER_CHARGE_GRAZE + _damage * SUPER_CHARGE_DAMAGE;
+        if !_shooting {
+            _energy += SUPER_CHARGE_IDLE;
+        }
+        if self.super_mode.charge(_energy) {
+            self.events.push(GameEvent::SuperReady);
+        }
+        if !self.super_mode.is_active() {
+            self.ship.t = ShotType::Normal;
+            self.ship.palette = Palette::Ship;
+        }
+        if self.super_flash > 0 {
+            self.super_flash -= 1;
+        }
+        self.max_bullets = max(self.max_bullets, self.bullets.len());
+        self.particles.update();
+    }
+
+    // the boss is defeated: bursts all over it, then the clear
+
+    fn update_boss_explosion(&mut self) {
+        self.particles.update();
+        let Some((_ticks, _boss)) = &mut self.boss_explosion else {
+            return;
+        };
+        *_ticks -= 1;
+        if *_ticks % BOSS_EXPLOSION_INTERVAL == 0 {
+            self.particles
+                .emit_in(&BOSS_EXPLOSION, _boss.x, _boss.y, _boss.w, _boss.h);
+        }
+        if *_ticks <= 0 {
+            self.boss_explosion = None;
+            self.stage = Stage::Gameclear;
+            self.events.push(GameEvent::Clear);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SEED: u64 = 42;
+
+    // weave left and right while holding Shoot, a bomb every few seconds
+    fn get_input(tick: i32) -> InputState {
+        let mut _input = InputState {
+            axis_x: if tick / 60 % 2 == 0 { 1.0 } else { -1.0 },
+            ..InputState::default()
+        };
+        _input.hold(Action::Shoot);
+        if tick % 300 == 299 {
+            _input.press(Action::Bomb);
+        }
+        _input
+    }
+
+    fn play(ticks: i32) -> World {
+        let mut _world = World::new(SEED);
+        _world.start_with_seed(SEED);
+        for i in 0..ticks {
+            _world.step(&get_input(i));
+        }
+        _world
+    }
+
+    #[test]
+    fn same_seed_and_inputs_give_the_same_run() {
+        let _first = play(1200);
+        let _second = play(1200);
+        assert!(_first.score > 0);
+        assert_eq!(_first.score, _second.score);
+        assert_eq!(_first.tick, _second.tick);
+        assert_eq!(_first.stage, _second.stage);
+    }
+
+    #[test]
+    fn pausing_does_not_advance_the_tick() {
+        let mut _world = play(60);
+        let _tick = _world.tick;
+        let mut _pause = InputState::default();
+        _pause.press(Action::Pause);
+        _world.step(&_pause);
+        assert_eq!(_world.stage, Stage::Paused);
+        for i in 0..120 {
+            _world.step(&get_input(i));
+        }
+        assert_eq!(_world.tick, _tick);
+        _world.step(&_pause);
+        assert_eq!(_world.stage, Stage::Playing);
+        _world.step(&get_input(0));
+        assert_eq!(_world.tick, _tick + 1);
+    }
+
+    #[test]
+    fn quitting_reports_the_abandoned_run() {
+        let mut _world = play(60);
+        let _run = _world.get_run_stats();
+        _world.pause();
+        _world.select_menu(MenuAction::Pause(PauseMenu::Quit));
+        assert_eq!(_world.stage, Stage::Openning);
+        assert!(_world.take_events().contains(&GameEvent::Abandoned(_run)));
+    }
+
+    #[test]
+    fn super_mode_lasts_super_time_ticks() {
+        let mut _world = play(60);
+        _world.ship.hp = 1000; // survive standing still
+        _world.super_mode = SuperMode::Ready;
+        let mut _bomb = InputState::default();
+        _bomb.press(Action::Bomb);
+        _world.step(&_bomb);
+        let mut _ticks = 0;
+        while _world.super_mode.is_active() {
+            _ticks += 1;
+            _world.step(&InputState::default());
+        }
+        assert_eq!(_ticks, SUPER_TIME);
+    }
+}
@@ -0,0 +1,46 @@
+// Leaderboard verification against a recorded run
+//
+// clear_normal.json is a Normal clear recorded by a bot. When a gameplay change
+// makes it stop verifying, the change breaks every submitted replay too.
+
+use wasm::leaderboard::{Submission, verify};
+
+const CLEAR_NORMAL: &str = include_str!("fixtures/clear_normal.json");
+
+fn get_submission() -> Submission {
+    serde_json::from_str(CLEAR_NORMAL).expect("parse the fixture")
+}
+
+#[test]
+fn recorded_clear_verifies() {
+    assert_eq!(verify(&get_submission()), Ok(()));
+}
+
+#[test]
+fn wrong_claims_are_rejected() {
+    let mut _submission = get_submission();
+    _submission.score += 10;
+    assert!(
+        verify(&_submission)
+            .unwrap_err()
+            .starts_with("score mismatch")
+    );
+
+    let mut _submission = get_submission();
+    _submission.milli_secondtime -= 1000;
+    assert!(
+        verify(&_submission)
+            .unwrap_err()
+            .starts_with("time mismatch")
+    );
+
+    let mut _submission = get_submission();
+    _submission
+        .replay
+        .inputs
+        .truncate(_submission.replay.inputs.len() / 2);
+    assert_eq!(
+        verify(&_submission),
+        Err("the replay doesn't clear the game".to_string())
+    );
+}
@@ -0,0 +1,60 @@
+// Offline audio against stored renders
+//
+// clear_normal_envelope.txt is the envelope of clear_normal.json rendered at
+// RENDER_RATE, one peak per ENVELOPE_WINDOW samples. Regenerate it when a sound
+// or a track changes on purpose.
+
+use wasm::leaderboard::Submission;
+use wasm::offline_audio::{get_envelope, render_replay, write_wav};
+
+const CLEAR_NORMAL: &str = include_str!("fixtures/clear_normal.json");
+const CLEAR_NORMAL_ENVELOPE: &str = include_str!("fixtures/clear_normal_envelope.txt");
+const RENDER_RATE: u32 = 22050;
+const ENVELOPE_WINDOW: usize = 2205; // 0.1 s.
+const TOLERANCE: f32 = 0.01; // float math differs a little between platforms
+
+#[test]
+fn replay_renders_the_stored_envelope() {
+    let _submission: Submission = serde_json::from_str(CLEAR_NORMAL).expect("parse the fixture");
+    let _samples = render_replay(&_submission.replay, RENDER_RATE);
+    let _envelope = get_envelope(&_samples, ENVELOPE_WINDOW);
+    let _stored: Vec<f32> = CLEAR_NORMAL_ENVELOPE
+        .lines()
+        .map(|_line| _line.parse().expect("parse the envelope"))
+        .collect();
+    assert_eq!(_envelope.len(), _stored.len());
+    for (i, (_peak, _expected)) in _envelope.iter().zip(&_stored).enumerate() {
+        assert!(
+            (_peak - _expected).abs() <= TOLERANCE,
+            "window {}: {} instead of {}",
+            i,
+            _peak,
+            _expected
+        );
+    }
+}
+
+#[test]
+fn wav_header_describes_16_bit_mono_pcm() {
+    let mut _wav = vec![];
+    write_wav(&mut _wav, &[0.0, 1.0, -1.0], 8000).unwrap();
+    let _u16 = |at: usize| u16::from_le_bytes([_wav[at], _wav[at + 1]]);
+    let _u32 = |at: usize| u32::from_le_bytes(_wav[at..at + 4].try_into().unwrap());
+
+    assert_eq!(_wav.len(), 44 + 6);
+    assert_eq!(&_wav[0..4], b"RIFF");
+    assert_eq!(_u32(4), 36 + 6);
+    assert_eq!(&_wav[8..16], b"WAVEfmt ");
+    assert_eq!(_u32(16), 16);
+    assert_eq!(_u16(20), 1); // PCM
+    assert_eq!(_u16(22), 1); // mono
+    assert_eq!(_u32(24), 8000);
+    assert_eq!(_u32(28), 8000 * 2); // bytes per second
+    assert_eq!(_u16(32), 2); // block align
+    assert_eq!(_u16(34), 16); // bits per sample
+    assert_eq!(&_wav[36..40], b"data");
+    assert_eq!(_u32(40), 6);
+    assert_eq!(_u16(44) as i16, 0);
+    assert_eq!(_u16(46) as i16, i16::MAX);
+    assert_eq!(_u16(48) as i16, -i16::MAX);
+}